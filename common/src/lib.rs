@@ -1 +1,3 @@
 pub mod api_response;
+pub mod sigv4;
+pub mod stats;
@@ -0,0 +1,27 @@
+/// Nearest-rank percentile of the sorted samples, the samples must not be empty
+pub fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank() {
+        let sorted = [15.0, 20.0, 35.0, 40.0, 50.0];
+
+        assert_eq!(percentile(&sorted, 0.0), 15.0);
+        assert_eq!(percentile(&sorted, 30.0), 20.0);
+        assert_eq!(percentile(&sorted, 40.0), 20.0);
+        assert_eq!(percentile(&sorted, 50.0), 35.0);
+        assert_eq!(percentile(&sorted, 100.0), 50.0);
+    }
+
+    #[test]
+    fn single_sample() {
+        assert_eq!(percentile(&[7.0], 99.0), 7.0);
+    }
+}
@@ -1,19 +1,170 @@
+use std::{collections::BTreeMap, fmt};
+
 use chrono::{DateTime, Utc};
+use common::sigv4;
 use serde::{Deserialize, Serialize};
+use url::Url;
 use uuid::Uuid;
 
 // Messages that can be sent or received
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Message {
-    WorkerJob { job_id: Uuid, payload: JobMessage },
-    WorkerResult { job_id: Uuid, result: ResultMessage },
-    WorkerStatus { status: StatusMessage },
+    WorkerJob {
+        job_id: Uuid,
+        payload: Box<JobMessage>,
+    },
+    WorkerResult {
+        job_id: Uuid,
+        result: Box<ResultMessage>,
+    },
+    WorkerStatus {
+        status: StatusMessage,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum WorkerJobType {
+    #[default]
+    CombinedDHP,
+    Upload,
+    Retrieval,
+    Iperf3,
+    RandomAccess,
+    LoadTest,
+    Soak,
+}
+
+/// iperf3 server of the raw TCP throughput test, the instances listen on consecutive ports
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Iperf3Server {
+    pub host: String,
+    pub port: u16,
+    /// Number of server instances, an instance runs one test at a time
+    pub port_count: u16,
+}
+
+/// Part of the DAG returned by the trustless gateway, `dag-scope` query parameter
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum DagScope {
+    #[default]
+    All,
+    Entity,
+    Block,
+}
+
+impl DagScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DagScope::All => "all",
+            DagScope::Entity => "entity",
+            DagScope::Block => "block",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum UploadMethod {
+    #[default]
+    Put,
+    Post,
+}
+
+/// Method used to measure the latency to the host
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum PingMethod {
+    /// ICMP echo, requires raw socket access
+    #[default]
+    Icmp,
+    /// TCP connect time to the port of the URL
+    Tcp,
+}
+
+/// HTTP protocol offered to the server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum HttpProtocol {
+    /// HTTP/1.1 only
+    #[default]
+    Http1,
+    /// HTTP/2 offered over ALPN, falls back to HTTP/1.1
+    Http2,
+    /// HTTP/3 over QUIC, not accepted by the scheduler until the workers can use QUIC
+    Http3,
+}
+
+impl HttpProtocol {
+    /// ALPN protocol identifiers offered in the TLS handshake
+    pub fn alpn_protocols(&self) -> &'static [&'static str] {
+        match self {
+            HttpProtocol::Http1 => &["http/1.1"],
+            HttpProtocol::Http2 | HttpProtocol::Http3 => &["h2", "http/1.1"],
+        }
+    }
+}
+
+/// Whether the requests of a job share connections
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionPolicy {
+    /// Requests and download streams share a connection pool, HTTP/2 multiplexes them
+    Reuse,
+    /// Every request and download stream opens a new connection
+    Fresh,
+}
+
+/// Request headers sent to the target, the values are left out of the debug output
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct RequestHeaders(pub BTreeMap<String, String>);
+
+impl RequestHeaders {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for RequestHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Access key signing the requests to an S3 compatible storage with SigV4
+#[derive(Serialize, Deserialize, Clone)]
+pub struct S3Signing {
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl S3Signing {
+    /// SigV4 headers of the request, signed at the current time
+    pub fn signed_headers(&self, method: &str, url: &Url) -> Vec<(&'static str, String)> {
+        let credentials = sigv4::Credentials {
+            access_key_id: &self.access_key_id,
+            secret_access_key: &self.secret_access_key,
+            session_token: self.session_token.as_deref(),
+        };
+
+        sigv4::sign(method, url, &self.region, &credentials, Utc::now())
+    }
+}
+
+impl fmt::Debug for S3Signing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Signing")
+            .field("region", &self.region)
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct JobMessage {
     pub job_id: Uuid,
     pub sub_job_id: Uuid,
+    /// Messages without a job type are the download jobs of the older schedulers
+    #[serde(default)]
+    pub job_type: WorkerJobType,
     pub url: String,
     pub start_time: DateTime<Utc>,
     pub download_start_time: DateTime<Utc>,
@@ -21,6 +172,63 @@ pub struct JobMessage {
     pub end_range: i64,
     pub excluded_workers: Vec<String>,
     pub log_interval_ms: i64,
+    #[serde(default = "default_streams_per_worker")]
+    pub streams_per_worker: i64,
+    pub upload_method: Option<UploadMethod>,
+    /// Maximum duration of the download or upload, the transfer stops after it
+    #[serde(default = "default_max_duration_secs")]
+    pub max_duration_secs: i64,
+    /// Latency measurement method, ICMP falling back to TCP when not set
+    pub ping_method: Option<PingMethod>,
+    /// Keep probing the latency through the download window
+    #[serde(default)]
+    pub measure_loaded_latency: bool,
+    /// Hash the downloaded range to compare the content between workers
+    #[serde(default)]
+    pub hash_content: bool,
+    /// Calculate the piece CID of the downloaded data, the range covers the whole piece
+    #[serde(default)]
+    pub verify_piece_cid: bool,
+    /// DAG scope of retrieval jobs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dag_scope: Option<DagScope>,
+    /// HTTP protocol of the download and HEAD requests, HTTP/1.1 when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_protocol: Option<HttpProtocol>,
+    /// Connection reuse, when not set every download stream opens its own connection and the
+    /// HEAD requests share one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_policy: Option<ConnectionPolicy>,
+    /// Headers of the download and HEAD requests, including the header of the job credential
+    #[serde(default, skip_serializing_if = "RequestHeaders::is_empty")]
+    pub headers: RequestHeaders,
+    /// Sign the download and HEAD requests for an S3 compatible storage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3_signing: Option<S3Signing>,
+    /// iperf3 server of the raw TCP throughput test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iperf3_server: Option<Iperf3Server>,
+    /// Size of the random range requests of random access and load test jobs, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_size: Option<i64>,
+    /// Requests per second the worker sends in load test jobs, its share of the target rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_rate: Option<f64>,
+    /// Length of the sampling windows of soak jobs, the windows start together on every worker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_interval_secs: Option<i64>,
+    /// URL and range of each worker by worker name in multi-URL jobs, the workers missing from it
+    /// download `url`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assignments: BTreeMap<String, RangeAssignment>,
+}
+
+/// URL and range downloaded by a worker
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RangeAssignment {
+    pub url: String,
+    pub start_range: i64,
+    pub end_range: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -33,6 +241,18 @@ pub struct ResultMessage {
     pub download_result: Result<DownloadResult, DownloadError>,
     pub ping_result: Result<PingResult, PingError>,
     pub head_result: Result<HeadResult, HeadError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_result: Option<Result<UploadResult, UploadError>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retrieval_result: Option<Result<RetrievalResult, RetrievalError>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iperf3_result: Option<Result<Iperf3Result, Iperf3Error>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub random_access_result: Option<Result<RandomAccessResult, RandomAccessError>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_test_result: Option<Result<LoadTestResult, LoadTestError>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soak_result: Option<Result<SoakResult, SoakError>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -45,6 +265,92 @@ pub struct DownloadResult {
     pub end_time: DateTime<Utc>,
     pub time_to_first_byte_ms: f64,
     pub second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_timing: Option<ConnectionTiming>,
+    /// Kernel TCP_INFO of the connection, sampled together with each of the interval logs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tcp_info_samples: Vec<(DateTime<Utc>, TcpInfoSample)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loaded_latency: Option<LoadedLatency>,
+    /// Validation of the response against the requested range, missing for combined streams
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range_check: Option<RangeCheck>,
+    /// Piece CID calculated from the downloaded data, only when the whole piece was downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece_cid: Option<String>,
+    /// Whether the downloaded data matches the piece CID of the URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece_cid_verified: Option<bool>,
+    /// Results of each parallel range stream, empty when the worker used a single stream
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streams: Vec<DownloadResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<ProtocolInfo>,
+}
+
+/// Protocol used to talk to the server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProtocolInfo {
+    /// HTTP version of the response, e.g. `HTTP/2.0`
+    pub http_version: String,
+    /// ALPN protocol negotiated on a probe connection, missing for plain HTTP
+    pub alpn: Option<String>,
+    /// TLS version negotiated on a probe connection, e.g. `TLSv1.3`
+    pub tls_version: Option<String>,
+    /// Alt-Svc header of the response, advertises HTTP/3 support
+    pub alt_svc: Option<String>,
+}
+
+/// Response to a range request, downloads of non compliant responses fail
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RangeCheck {
+    pub status_code: u16,
+    pub content_range: String,
+    pub expected_bytes: usize,
+    /// Whether the whole range was downloaded before the maximum download duration
+    pub complete: bool,
+    /// Hex encoded SHA-256 of the range, only for complete downloads with hashing enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Latency probes sent while the download is running, on every log interval
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoadedLatency {
+    pub method: PingMethod,
+    /// Round trip time in milliseconds, missing for lost probes
+    pub samples: Vec<(DateTime<Utc>, Option<f64>)>,
+}
+
+/// Duration of each phase of a probe connection in milliseconds, phases do not overlap
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConnectionTiming {
+    pub ip_address: String,
+    pub dns_ms: f64,
+    pub connect_ms: f64,
+    /// Missing for plain HTTP connections
+    pub tls_ms: Option<f64>,
+    pub request_sent_ms: f64,
+    /// Time from the request being sent to the first byte of the response
+    pub ttfb_ms: f64,
+    pub total_ms: f64,
+}
+
+/// Snapshot of the kernel TCP_INFO of a connection, rates are in bytes per second
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TcpInfoSample {
+    pub rtt_us: u32,
+    pub rtt_var_us: u32,
+    pub min_rtt_us: u32,
+    pub rcv_rtt_us: u32,
+    pub snd_cwnd: u32,
+    pub rcv_space: u32,
+    pub retransmits: u32,
+    pub total_retransmits: u32,
+    pub lost: u32,
+    pub delivery_rate: u64,
+    pub pacing_rate: u64,
+    pub bytes_received: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -52,36 +358,274 @@ pub struct IntervalBytes(pub usize);
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AccumulatingBytes(pub usize);
 
+pub type DownloadError = MeasurementError;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct DownloadError {
-    pub error: String,
+pub struct UploadResult {
+    pub total_bytes: usize,
+    pub elapsed_secs: f64,
+    pub upload_speed: f64,
+    pub job_start_time: DateTime<Utc>,
+    pub upload_start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub status_code: Option<u16>,
+    pub second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)>,
+}
+
+pub type UploadError = MeasurementError;
+
+/// Retrieval of a CAR from a trustless gateway, the speed is measured over the CAR bytes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetrievalResult {
+    pub total_bytes: usize,
+    pub elapsed_secs: f64,
+    pub retrieval_speed: f64,
+    pub job_start_time: DateTime<Utc>,
+    pub retrieval_start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub time_to_first_byte_ms: f64,
+    pub second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)>,
+    pub blocks: usize,
+    /// Blocks whose data matched the hash of their CID
+    pub verified_blocks: usize,
+    /// Blocks with a hash function the worker can't verify
+    pub unverified_blocks: usize,
+    pub block_bytes: usize,
+    /// Whether the whole CAR was received before the maximum duration
+    pub complete: bool,
+}
+
+pub type RetrievalError = MeasurementError;
+
+/// Raw TCP throughput received from an iperf3 server in reverse mode, the server sends the data
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Iperf3Result {
+    /// Server instance that accepted the test, `host:port`
+    pub server: String,
+    pub streams: usize,
+    pub total_bytes: usize,
+    pub elapsed_secs: f64,
+    pub iperf3_speed: f64,
+    pub job_start_time: DateTime<Utc>,
+    pub transfer_start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)>,
+    /// Bytes the server reports as sent
+    pub server_bytes: Option<usize>,
+    /// TCP retransmits on the server, when its platform reports them
+    pub server_retransmits: Option<i64>,
+}
+
+pub type Iperf3Error = MeasurementError;
+
+/// Small range requests at random offsets of the file, the time to first byte shows the seek time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RandomAccessResult {
+    pub read_size: usize,
+    /// Completed requests, the failed ones are counted apart
+    pub requests: usize,
+    pub failed_requests: usize,
+    pub total_bytes: usize,
+    pub elapsed_secs: f64,
+    pub requests_per_sec: f64,
+    pub job_start_time: DateTime<Utc>,
+    pub random_access_start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub avg_ttfb_ms: f64,
+    /// Time to first byte of the successful requests in milliseconds, the loss is the share of
+    /// failed requests
+    pub ttfb_ms: LatencyStats,
+}
+
+pub type RandomAccessError = MeasurementError;
+
+/// Range requests sent at a fixed rate whether or not the previous ones completed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoadTestResult {
+    pub request_rate: f64,
+    pub read_size: usize,
+    pub requests: usize,
+    pub succeeded: usize,
+    pub success_rate: f64,
+    /// Successful requests per second over the window
+    pub achieved_rate: f64,
+    pub job_start_time: DateTime<Utc>,
+    pub load_test_start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub status_codes: BTreeMap<u16, usize>,
+    /// Failed requests by error kind
+    pub errors: BTreeMap<String, usize>,
+    /// Completion time of the successful requests in milliseconds
+    pub latency_ms: LatencyStats,
+    /// Left out of the job details unless the logs are requested
+    #[serde(default)]
+    pub per_second: Vec<LoadTestSecond>,
+}
+
+/// Requests sent in one second of the load test window
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LoadTestSecond {
+    pub second: u64,
+    pub requests: usize,
+    pub succeeded: usize,
+    pub success_rate: f64,
+    pub status_codes: BTreeMap<u16, usize>,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+}
+
+pub type LoadTestError = MeasurementError;
+
+/// Continuous download sampled in windows of a fixed length over a long duration
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SoakResult {
+    pub streams: usize,
+    pub total_bytes: usize,
+    pub elapsed_secs: f64,
+    pub soak_speed: f64,
+    pub job_start_time: DateTime<Utc>,
+    pub soak_start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// Range requests that failed and were retried
+    pub failed_requests: usize,
+    pub sample_interval_secs: u64,
+    pub samples: Vec<SoakSample>,
 }
 
+/// Bytes received in one sampling window of the soak
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SoakSample {
+    /// Start of the window since the start of the soak
+    pub offset_secs: u64,
+    pub bytes: usize,
+    pub speed: f64,
+}
+
+pub type SoakError = MeasurementError;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PingResult {
     pub min: f64,
     pub max: f64,
     pub avg: f64,
     pub ip_address: String,
+    #[serde(default)]
+    pub method: PingMethod,
+    #[serde(flatten)]
+    pub stats: LatencyStats,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct PingError {
-    pub error: String,
-}
+pub type PingError = MeasurementError;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HeadResult {
     pub min: f64,
     pub max: f64,
     pub avg: f64,
+    #[serde(flatten)]
+    pub stats: LatencyStats,
+    /// Status code of each successful request
+    #[serde(default)]
+    pub status_codes: Vec<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<ProtocolInfo>,
+}
+
+fn default_streams_per_worker() -> i64 {
+    1
 }
 
+/// Duration the workers stopped the download after before it was sent with the job
+fn default_max_duration_secs() -> i64 {
+    60
+}
+
+/// Distribution of the latency samples, in the same unit as the samples
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LatencyStats {
+    /// Percentage of the probes without a response
+    pub loss_percent: f64,
+    pub stddev: f64,
+    /// Mean absolute difference between consecutive samples
+    pub jitter: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    /// Samples of the distribution, at most 1000 spread evenly over the run
+    pub samples: Vec<f64>,
+}
+
+pub type HeadError = MeasurementError;
+
+/// Failure of a measurement, the kind is stable and can be aggregated, the error is for humans
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct HeadError {
+pub struct MeasurementError {
+    #[serde(flatten)]
+    pub kind: ErrorKind,
     pub error: String,
 }
 
+impl MeasurementError {
+    pub fn new(kind: ErrorKind, error: impl Into<String>) -> Self {
+        Self {
+            kind,
+            error: error.into(),
+        }
+    }
+}
+
+/// Cause of a failed measurement, serialized as `{"kind": "http_status", "code": 503}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ErrorKind {
+    InvalidUrl,
+    Dns,
+    ConnectRefused,
+    ConnectTimeout,
+    Connect,
+    Tls,
+    Request,
+    HttpStatus {
+        code: u16,
+    },
+    BodyTimeout,
+    Body,
+    ZeroBytes,
+    TimeSync,
+    RangeNotSupported,
+    ContentRangeMismatch,
+    ByteCountMismatch,
+    PieceCid,
+    UnexpectedContentType,
+    CarParse,
+    BlockHashMismatch,
+    PingClient,
+    PacketLoss,
+    /// Every iperf3 server instance is running another test
+    ServerBusy,
+    /// Unexpected state or error message of the iperf3 server
+    Iperf3Protocol,
+    /// Measurement is not part of the job type
+    NotApplicable,
+    Aborted,
+    Internal,
+}
+
+impl ErrorKind {
+    /// Name of the kind, the status code is appended for `http_status`, e.g. `http_status_503`
+    pub fn name(&self) -> String {
+        match self {
+            ErrorKind::HttpStatus { code } => format!("http_status_{code}"),
+            kind => serde_json::to_value(kind)
+                .ok()
+                .and_then(|value| value.get("kind")?.as_str().map(str::to_string))
+                .unwrap_or_default(),
+        }
+    }
+}
+
 impl ResultMessage {
     pub fn aborted(
         run_id: Uuid,
@@ -96,13 +640,15 @@ impl ResultMessage {
             sub_job_id,
             worker_name,
             is_success: false,
-            download_result: Err(DownloadError {
-                error: error.clone(),
-            }),
-            ping_result: Err(PingError {
-                error: error.clone(),
-            }),
-            head_result: Err(HeadError { error }),
+            download_result: Err(DownloadError::new(ErrorKind::Aborted, error.clone())),
+            ping_result: Err(PingError::new(ErrorKind::Aborted, error.clone())),
+            head_result: Err(HeadError::new(ErrorKind::Aborted, error)),
+            upload_result: None,
+            retrieval_result: None,
+            iperf3_result: None,
+            random_access_result: None,
+            load_test_result: None,
+            soak_result: None,
         }
     }
 }
@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use rabbitmq::{AccumulatingBytes, IntervalBytes};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Share of the peak interval speed that ends the TCP slow start of a worker
+const STEADY_STATE_PEAK_PERCENT: f64 = 80.0;
+
+/// Interval logs of the download of a worker
+pub(crate) struct WorkerLogs {
+    worker_name: String,
+    download_start_time: Option<DateTime<Utc>>,
+    logs: Vec<IntervalLog>,
+}
+
+impl WorkerLogs {
+    /// Interval logs of the download result, without logs the download failed or was stripped
+    pub(crate) fn parse(
+        worker_name: String,
+        download_start_time: Option<serde_json::Value>,
+        logs: serde_json::Value,
+    ) -> Option<Self> {
+        Some(WorkerLogs {
+            worker_name,
+            download_start_time: download_start_time
+                .and_then(|time| serde_json::from_value(time).ok()),
+            logs: serde_json::from_value(logs).ok()?,
+        })
+    }
+}
+
+/// Aggregate download speed over the intervals all workers were downloading
+pub(crate) struct OverlapThroughput {
+    pub(crate) speed: f64,
+    pub(crate) window_secs: f64,
+}
+
+/// Interval log of a download, the bytes since the previous log and since the start
+type IntervalLog = (DateTime<Utc>, IntervalBytes, AccumulatingBytes);
+
+/// Bytes downloaded by all workers in the log interval ending at the time
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AggregateInterval {
+    time: DateTime<Utc>,
+    bytes: usize,
+    speed: f64,
+    /// Workers that logged the interval
+    workers: usize,
+    /// Whether every worker was downloading for the whole interval
+    in_overlap: bool,
+}
+
+/// Download speed of a worker after the TCP slow start, the ramp up ends with the first interval
+/// reaching 80% of the peak interval speed
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WorkerSteadyState {
+    worker_name: String,
+    steady_state_speed: f64,
+    peak_interval_speed: f64,
+    /// Start of the first interval reaching the share of the peak interval speed
+    steady_state_start: DateTime<Utc>,
+    /// Time from the start of the download to the steady state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ramp_up_secs: Option<f64>,
+}
+
+/// Aggregates of the interval logs of the workers of a download sub job, computed once when the
+/// sub job completes and stored in its details
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct DownloadAggregates {
+    pub(crate) overlap_download_speed: Option<f64>,
+    pub(crate) overlap_window_secs: Option<f64>,
+    pub(crate) aggregate_intervals: Vec<AggregateInterval>,
+    pub(crate) steady_state_speed: Option<f64>,
+    pub(crate) average_ramp_up_secs: Option<f64>,
+    pub(crate) steady_state_workers: Vec<WorkerSteadyState>,
+}
+
+/// Aggregate the interval logs of the workers into the overlap and steady state speeds
+pub(crate) fn aggregate_downloads(
+    worker_logs: &[WorkerLogs],
+    log_interval_ms: i64,
+) -> DownloadAggregates {
+    let (overlap, aggregate_intervals) = aggregate_interval_logs(worker_logs, log_interval_ms);
+    let steady_states: Vec<(&WorkerLogs, WorkerSteadyState)> = worker_logs
+        .iter()
+        .filter_map(|worker| Some((worker, steady_state(worker)?)))
+        .collect();
+    let steady_state_speed = steady_window_speed(&steady_states, log_interval_ms);
+    let steady_state_workers: Vec<WorkerSteadyState> = steady_states
+        .into_iter()
+        .map(|(_, steady_state)| steady_state)
+        .collect();
+    let ramp_ups: Vec<f64> = steady_state_workers
+        .iter()
+        .filter_map(|worker| worker.ramp_up_secs)
+        .collect();
+
+    DownloadAggregates {
+        overlap_download_speed: overlap.as_ref().map(|o| o.speed),
+        overlap_window_secs: overlap.as_ref().map(|o| o.window_secs),
+        aggregate_intervals,
+        steady_state_speed,
+        average_ramp_up_secs: Some(ramp_ups.iter().sum::<f64>() / ramp_ups.len() as f64)
+            .filter(|_| !ramp_ups.is_empty()),
+        steady_state_workers,
+    }
+}
+
+/// Add up the interval logs of the workers of a sub job
+///
+/// The logs are aligned to the log interval on every worker, each covering the interval ending at
+/// its boundary. The first interval of a worker is partial, so the overlap window starts after the
+/// latest first interval and ends with the earliest last interval.
+pub(crate) fn aggregate_interval_logs(
+    worker_logs: &[WorkerLogs],
+    log_interval_ms: i64,
+) -> (Option<OverlapThroughput>, Vec<AggregateInterval>) {
+    let log_interval_ms = log_interval_ms.max(1);
+
+    let mut intervals: BTreeMap<i64, (usize, usize)> = BTreeMap::new();
+    let mut overlap_start = i64::MIN;
+    let mut overlap_end = i64::MAX;
+    for worker in worker_logs.iter().filter(|worker| !worker.logs.is_empty()) {
+        let boundaries: Vec<(i64, usize)> = worker
+            .logs
+            .iter()
+            .map(|(time, interval_bytes, _)| {
+                let millis = time.timestamp_millis();
+                (millis - millis % log_interval_ms, interval_bytes.0)
+            })
+            .collect();
+
+        overlap_start = overlap_start.max(boundaries[0].0 + log_interval_ms);
+        overlap_end = overlap_end.min(boundaries[boundaries.len() - 1].0);
+        for (boundary, bytes) in boundaries {
+            let (interval_bytes, workers) = intervals.entry(boundary).or_default();
+            *interval_bytes += bytes;
+            *workers += 1;
+        }
+    }
+
+    // Stalled intervals have no log, the window is measured from the boundaries
+    let overlap = (overlap_start <= overlap_end).then(|| {
+        let window_secs = (overlap_end - overlap_start + log_interval_ms) as f64 / 1000.0;
+        let bytes: usize = intervals
+            .range(overlap_start..=overlap_end)
+            .map(|(_, (bytes, _))| bytes)
+            .sum();
+        OverlapThroughput {
+            speed: interval_speed(bytes, window_secs),
+            window_secs,
+        }
+    });
+
+    let aggregate_intervals = intervals
+        .into_iter()
+        .filter_map(|(boundary, (bytes, workers))| {
+            Some(AggregateInterval {
+                time: DateTime::from_timestamp_millis(boundary)?,
+                bytes,
+                speed: interval_speed(bytes, log_interval_ms as f64 / 1000.0),
+                workers,
+                in_overlap: (overlap_start..=overlap_end).contains(&boundary),
+            })
+        })
+        .collect();
+
+    (overlap, aggregate_intervals)
+}
+
+/// Download speed of the worker from the first interval reaching the share of the peak interval
+/// speed, the slower intervals before are the TCP slow start
+fn steady_state(worker: &WorkerLogs) -> Option<WorkerSteadyState> {
+    // Each interval runs from the previous log, a stalled interval is covered by the next log.
+    // The first interval starts with the download, its length is unknown.
+    let intervals: Vec<(DateTime<Utc>, DateTime<Utc>, usize)> = worker
+        .logs
+        .windows(2)
+        .map(|pair| (pair[0].0, pair[1].0, pair[1].1 .0))
+        .filter(|(start, end, _)| end > start)
+        .collect();
+    let secs =
+        |start: DateTime<Utc>, end: DateTime<Utc>| (end - start).num_milliseconds() as f64 / 1000.0;
+
+    let speeds: Vec<f64> = intervals
+        .iter()
+        .map(|(start, end, bytes)| interval_speed(*bytes, secs(*start, *end)))
+        .collect();
+    let peak_interval_speed = speeds.iter().copied().fold(0.0, f64::max);
+    if peak_interval_speed <= 0.0 {
+        return None;
+    }
+
+    let ramp_up_end = speeds
+        .iter()
+        .position(|speed| *speed >= peak_interval_speed * STEADY_STATE_PEAK_PERCENT / 100.0)?;
+    let steady_intervals = &intervals[ramp_up_end..];
+    let steady_state_start = steady_intervals[0].0;
+    let steady_state_end = steady_intervals[steady_intervals.len() - 1].1;
+
+    Some(WorkerSteadyState {
+        worker_name: worker.worker_name.clone(),
+        steady_state_speed: interval_speed(
+            steady_intervals.iter().map(|(_, _, bytes)| bytes).sum(),
+            secs(steady_state_start, steady_state_end),
+        ),
+        peak_interval_speed,
+        steady_state_start,
+        ramp_up_secs: worker.download_start_time.map(|start| {
+            ((steady_state_start - start).num_milliseconds() as f64 / 1000.0).max(0.0)
+        }),
+    })
+}
+
+/// Aggregate download speed of the workers over the window all of them were in the steady state
+///
+/// The window starts with the latest steady state start and ends with the earliest last log, on
+/// the interval boundaries like the overlap window.
+fn steady_window_speed(
+    steady_states: &[(&WorkerLogs, WorkerSteadyState)],
+    log_interval_ms: i64,
+) -> Option<f64> {
+    let log_interval_ms = log_interval_ms.max(1);
+    let boundary = |time: &DateTime<Utc>| {
+        let millis = time.timestamp_millis();
+        millis - millis % log_interval_ms
+    };
+
+    let window_start = steady_states
+        .iter()
+        .map(|(_, steady_state)| boundary(&steady_state.steady_state_start))
+        .max()?;
+    let window_end = steady_states
+        .iter()
+        .filter_map(|(worker, _)| worker.logs.last())
+        .map(|(time, _, _)| boundary(time))
+        .min()?;
+    if window_end <= window_start {
+        return None;
+    }
+
+    let bytes = steady_states
+        .iter()
+        .flat_map(|(worker, _)| &worker.logs)
+        .filter(|(time, _, _)| (window_start + 1..=window_end).contains(&boundary(time)))
+        .map(|(_, interval_bytes, _)| interval_bytes.0)
+        .sum();
+
+    Some(interval_speed(
+        bytes,
+        (window_end - window_start) as f64 / 1000.0,
+    ))
+}
+
+/// Speed in megabits per second, the same unit as the download speed of the workers
+fn interval_speed(bytes: usize, secs: f64) -> f64 {
+    (bytes as f64 * 8.0) / (secs * 1024.0 * 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker_logs(logs: &[(i64, usize)]) -> WorkerLogs {
+        let mut total_bytes = 0;
+        WorkerLogs {
+            worker_name: String::new(),
+            download_start_time: None,
+            logs: logs
+                .iter()
+                .map(|&(millis, bytes)| {
+                    total_bytes += bytes;
+                    (
+                        DateTime::from_timestamp_millis(millis).unwrap(),
+                        IntervalBytes(bytes),
+                        AccumulatingBytes(total_bytes),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn aggregate_interval_logs_measures_the_overlap_window() {
+        let workers = [
+            worker_logs(&[(1_003, 100), (2_001, 200), (3_002, 300), (4_001, 400)]),
+            worker_logs(&[(2_005, 10), (3_005, 20), (4_005, 30), (5_005, 40)]),
+            worker_logs(&[]),
+        ];
+
+        let (overlap, intervals) = aggregate_interval_logs(&workers, 1000);
+
+        // The first interval of each worker is partial, the window covers 3000 and 4000
+        let overlap = overlap.unwrap();
+        assert_eq!(overlap.window_secs, 2.0);
+        assert_eq!(overlap.speed, interval_speed(300 + 20 + 400 + 30, 2.0));
+
+        let intervals: Vec<_> = intervals
+            .iter()
+            .map(|interval| {
+                (
+                    interval.time.timestamp_millis(),
+                    interval.bytes,
+                    interval.workers,
+                    interval.in_overlap,
+                )
+            })
+            .collect();
+        assert_eq!(
+            intervals,
+            [
+                (1_000, 100, 1, false),
+                (2_000, 210, 2, false),
+                (3_000, 320, 2, true),
+                (4_000, 430, 2, true),
+                (5_000, 40, 1, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_interval_logs_without_overlap() {
+        let workers = [
+            worker_logs(&[(1_000, 100), (2_000, 100)]),
+            worker_logs(&[(5_000, 100), (6_000, 100)]),
+        ];
+
+        let (overlap, intervals) = aggregate_interval_logs(&workers, 1000);
+
+        assert!(overlap.is_none());
+        assert_eq!(intervals.len(), 4);
+        assert!(intervals.iter().all(|interval| !interval.in_overlap));
+    }
+
+    #[test]
+    fn steady_state_measures_the_intervals_between_the_logs() {
+        // The log of 5000 is missing, the next log covers the stalled interval
+        let mut worker = worker_logs(&[
+            (1_005, 100),
+            (2_003, 400),
+            (3_001, 1000),
+            (4_002, 1000),
+            (6_004, 2000),
+        ]);
+        worker.download_start_time = DateTime::from_timestamp_millis(0);
+
+        let steady_state = steady_state(&worker).unwrap();
+
+        assert_eq!(steady_state.steady_state_start.timestamp_millis(), 2_003);
+        assert_eq!(steady_state.ramp_up_secs, Some(2.003));
+        assert_eq!(steady_state.steady_state_speed, interval_speed(4000, 4.001));
+        assert_eq!(
+            steady_state.peak_interval_speed,
+            interval_speed(1000, 0.998)
+        );
+    }
+
+    #[test]
+    fn steady_window_speed_intersects_the_steady_windows() {
+        let ramping = worker_logs(&[
+            (1_005, 100),
+            (2_003, 400),
+            (3_001, 1000),
+            (4_002, 1000),
+            (6_004, 2000),
+        ]);
+        let steady = worker_logs(&[
+            (1_002, 1000),
+            (2_002, 1000),
+            (3_002, 1000),
+            (4_002, 1000),
+            (5_002, 1000),
+            (6_002, 1000),
+            (7_002, 1000),
+        ]);
+        let steady_states: Vec<_> = [&ramping, &steady]
+            .into_iter()
+            .map(|worker| (worker, steady_state(worker).unwrap()))
+            .collect();
+
+        // The window runs from 2000 to 6000, when both workers were past the ramp up
+        assert_eq!(
+            steady_window_speed(&steady_states, 1000),
+            Some(interval_speed(4000 + 4000, 4.0))
+        );
+        assert_eq!(steady_window_speed(&steady_states[..0], 1000), None);
+    }
+
+    #[test]
+    fn aggregate_downloads_are_read_back_from_the_details() {
+        let workers = [
+            worker_logs(&[(1_000, 100), (2_000, 400), (3_000, 1000), (4_000, 1000)]),
+            worker_logs(&[(1_000, 100), (2_000, 1000), (3_000, 1000), (4_000, 1000)]),
+        ];
+        let aggregates = aggregate_downloads(&workers, 1000);
+
+        let stored: DownloadAggregates =
+            serde_json::from_value(serde_json::to_value(&aggregates).unwrap()).unwrap();
+
+        assert_eq!(
+            stored.overlap_download_speed,
+            aggregates.overlap_download_speed
+        );
+        assert_eq!(stored.steady_state_speed, aggregates.steady_state_speed);
+        assert_eq!(stored.aggregate_intervals.len(), 4);
+        assert_eq!(stored.steady_state_workers.len(), 2);
+    }
+}
@@ -3,15 +3,18 @@ use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
 use utoipa::{Modify, OpenApi};
 
 use crate::{
+    aggregation,
     api::{
+        credentials::{create_credential, delete_credential, get_credentials},
         healthcheck,
-        jobs::{cancel_job, create_job, get_job, get_jobs},
+        jobs::{cancel_job, create_job, get_job, get_jobs, job_summary, rerun_job},
         services::{
             create_service, delete_service, get_services, services_info, services_scale_down,
             services_scale_down_all, services_scale_up, update_service,
         },
     },
-    job_repository, service_repository, service_scaler, sub_job_repository,
+    credential_repository, credentials, job_repository, service_repository, service_scaler,
+    sub_job_repository,
 };
 
 // SecurityAddon struct to add security schemes
@@ -47,6 +50,7 @@ Creating a Job requires an endpoint URL to a file (e.g. piece) as well as a rout
         cancel_job::handle_cancel_job,
         get_jobs::handle_get_jobs,
         get_job::handle_get_job,
+        rerun_job::handle_rerun_job,
         // Services
         create_service::handle_create_service,
         delete_service::handle_delete_service,
@@ -55,7 +59,11 @@ Creating a Job requires an endpoint URL to a file (e.g. piece) as well as a rout
         services_info::handle_services_info,
         services_scale_up::handle_services_scale_up,
         services_scale_down::handle_services_scale_down,
-        services_scale_down_all::handle_services_scale_down_all
+        services_scale_down_all::handle_services_scale_down_all,
+        // Credentials
+        create_credential::handle_create_credential,
+        get_credentials::handle_get_credentials,
+        delete_credential::handle_delete_credential
     ),
     components(
         schemas(
@@ -71,7 +79,21 @@ Creating a Job requires an endpoint URL to a file (e.g. piece) as well as a rout
 
             get_job::GetJobPathParams,
             get_job::GetJobResponse,
-            get_job::JobSummary,
+
+            job_summary::JobSummary,
+            job_summary::SourceDownloadSpeed,
+            job_summary::ConnectionTimingSummary,
+            job_summary::LatencySummary,
+            job_summary::LoadedLatencySummary,
+            job_summary::RateLatencyPoint,
+            job_summary::SaturationSearchReport,
+            job_summary::SoakReport,
+            job_summary::SubJobErrorHistogram,
+
+            aggregation::AggregateInterval,
+            aggregation::WorkerSteadyState,
+
+            rerun_job::RerunJobPathParams,
 
             // Services Schemas
             create_service::CreateServiceInput,
@@ -101,6 +123,15 @@ Creating a Job requires an endpoint URL to a file (e.g. piece) as well as a rout
             services_scale_down_all::ServiceScaleDownAllResponse,
             services_scale_down_all::ServiceWithInfo,
 
+            // Credentials Schemas
+            create_credential::CreateCredentialInput,
+            create_credential::CreateCredentialResponse,
+
+            get_credentials::GetCredentialsResponse,
+
+            delete_credential::DeleteCredentialPathInput,
+            delete_credential::DeleteCredentialResponse,
+
             healthcheck::HealthcheckResponse,
 
             // Common Schemas
@@ -109,12 +140,16 @@ Creating a Job requires an endpoint URL to a file (e.g. piece) as well as a rout
             // Additional Schemas
             job_repository::Job,
             job_repository::JobStatus,
+            job_repository::JobType,
             job_repository::JobWithSubJobsWithData,
             job_repository::SubJobWithData,
             job_repository::WorkerData,
             job_repository::JobDetails,
             job_repository::JobWithSubJobs,
 
+            credential_repository::Credential,
+            credentials::CredentialSecret,
+
             service_repository::Service,
             service_repository::ServiceWithTopics,
 
@@ -131,6 +166,7 @@ Creating a Job requires an endpoint URL to a file (e.g. piece) as well as a rout
         (name = "Healthcheck", description = "Healthcheck API"),
         (name = "Jobs", description = "Job management APIs"),
         (name = "Services", description = "Service management APIs"),
+        (name = "Credentials", description = "Target credential management APIs"),
     )
 )]
 pub struct ApiDoc;
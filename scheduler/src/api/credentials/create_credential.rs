@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use axum::{
+    debug_handler,
+    extract::{Json, State},
+};
+use axum_extra::extract::WithRejection;
+use common::api_response::*;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    credential_repository::Credential,
+    credentials::{self, CredentialSecret},
+    state::AppState,
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateCredentialInput {
+    #[schema(example = "provider-gateway-token")]
+    pub name: String,
+    pub secret: CredentialSecret,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateCredentialResponse(pub Credential);
+
+/// Store an encrypted credential for authenticated targets
+#[utoipa::path(
+    post,
+    path = "/credentials",
+    request_body(content = CreateCredentialInput),
+    description = r#"
+**Store an encrypted credential for authenticated targets.**
+
+Jobs reference the credential with `credential_id`, the secret is sent to the target as a request header and is never returned by the API. `S3` access keys sign the requests of jobs with an `s3_source` instead.
+"#,
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Created Credential", body = CreateCredentialResponse),
+        (status = 400, description = "Bad Request", body = ErrorResponse),
+        (status = 500, description = "Internal Server Error", body = ErrorResponse),
+    ),
+    tags = ["Credentials"],
+)]
+#[debug_handler]
+pub async fn handle_create_credential(
+    State(state): State<Arc<AppState>>,
+    WithRejection(Json(payload), _): WithRejection<
+        Json<CreateCredentialInput>,
+        ApiResponse<ErrorResponse>,
+    >,
+) -> Result<ApiResponse<CreateCredentialResponse>, ApiResponse<()>> {
+    // Validation
+    if payload.name.is_empty() {
+        return Err(bad_request("Field 'name' cannot be empty"));
+    }
+    if let Some((name, value)) = payload.secret.header() {
+        if reqwest::header::HeaderName::try_from(name).is_err()
+            || reqwest::header::HeaderValue::try_from(value).is_err()
+        {
+            return Err(bad_request("Secret is not a valid header"));
+        }
+    }
+
+    let credential_id = Uuid::new_v4();
+    let encrypted = credentials::encrypt(&credential_id, &payload.secret)
+        .inspect_err(|e| error!("Failed to encrypt credential: {:?}", e))
+        .map_err(|_| internal_server_error("Failed to encrypt credential"))?;
+
+    let credential = state
+        .repo
+        .credential
+        .create_credential(&credential_id, &payload.name, &encrypted)
+        .await
+        .inspect_err(|e| {
+            error!("CredentialRepository create credential error: {:?}", e);
+        })
+        .map_err(|_| internal_server_error("Failed to create credential"))?;
+
+    Ok(ok_response(CreateCredentialResponse(credential)))
+}
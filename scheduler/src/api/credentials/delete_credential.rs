@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+};
+use axum_extra::extract::WithRejection;
+use common::api_response::*;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{credential_repository::Credential, state::AppState};
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct DeleteCredentialPathInput {
+    pub credential_id: Uuid,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeleteCredentialResponse(pub Credential);
+
+/// Delete a credential
+#[utoipa::path(
+    delete,
+    path = "/credentials/{credential_id}",
+    params(DeleteCredentialPathInput),
+    description = r#"
+**Delete a credential.**
+
+Jobs referencing the credential that did not start yet will fail.
+"#,
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Deleted Credential", body = DeleteCredentialResponse),
+        (status = 404, description = "Not Found", body = ErrorResponse),
+        (status = 500, description = "Internal Server Error", body = ErrorResponse),
+    ),
+    tags = ["Credentials"],
+)]
+#[debug_handler]
+pub async fn handle_delete_credential(
+    State(state): State<Arc<AppState>>,
+    WithRejection(Path(path), _): WithRejection<
+        Path<DeleteCredentialPathInput>,
+        ApiResponse<ErrorResponse>,
+    >,
+) -> Result<ApiResponse<DeleteCredentialResponse>, ApiResponse<()>> {
+    let credential = state
+        .repo
+        .credential
+        .delete_credential_by_id(&path.credential_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => not_found("Credential not found"),
+            _ => {
+                error!("CredentialRepository delete credential error: {:?}", e);
+                internal_server_error("Failed to delete credential")
+            }
+        })?;
+
+    Ok(ok_response(DeleteCredentialResponse(credential)))
+}
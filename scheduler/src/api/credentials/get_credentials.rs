@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use axum::{debug_handler, extract::State};
+use common::api_response::*;
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{credential_repository::Credential, state::AppState};
+
+#[derive(Serialize, ToSchema)]
+pub struct GetCredentialsResponse(pub Vec<Credential>);
+
+/// Get all credentials without their secrets
+#[utoipa::path(
+    get,
+    path = "/credentials",
+    description = r#"
+**Get all credentials without their secrets.**
+"#,
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Get Credentials", body = GetCredentialsResponse),
+        (status = 500, description = "Internal Server Error", body = ErrorResponse),
+    ),
+    tags = ["Credentials"],
+)]
+#[debug_handler]
+pub async fn handle_get_credentials(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<GetCredentialsResponse>, ApiResponse<()>> {
+    let credentials = state
+        .repo
+        .credential
+        .get_credentials()
+        .await
+        .inspect_err(|e| {
+            error!("CredentialRepository get credentials error: {:?}", e);
+        })
+        .map_err(|_| internal_server_error("Failed to get credentials"))?;
+
+    Ok(ok_response(GetCredentialsResponse(credentials)))
+}
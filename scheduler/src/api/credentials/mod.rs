@@ -0,0 +1,3 @@
+pub mod create_credential;
+pub mod delete_credential;
+pub mod get_credentials;
@@ -72,7 +72,7 @@ pub async fn handle_cancel_job(
             bad_request("Failed to cancel sub jobs")
         })?;
 
-    let job = state
+    let mut job = state
         .repo
         .job
         .get_job_by_id_with_subjobs(&job_id)
@@ -82,5 +82,7 @@ pub async fn handle_cancel_job(
             bad_request("Failed to get job")
         })?;
 
+    job.details.redact_headers();
+
     Ok(ok_response(CancelJobResponse(job)))
 }
@@ -4,8 +4,13 @@ use axum::{
 };
 use axum_extra::extract::WithRejection;
 use color_eyre::Result;
-use common::api_response::*;
-use rand::Rng;
+use common::{api_response::*, sigv4::object_url};
+use rabbitmq::{
+    ConnectionPolicy, DagScope, HttpProtocol, Iperf3Server, PingMethod, RangeAssignment,
+    RequestHeaders, S3Signing, UploadMethod,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -15,17 +20,23 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
-    job_repository::{Job, JobDetails, JobStatus},
+    credentials::{encrypt_headers, request_auth},
+    job_repository::{Job, JobDetails, JobStatus, JobType, S3Source},
     state::AppState,
     sub_job_repository::{SubJob, SubJobDetails, SubJobStatus, SubJobType},
 };
 
 #[derive(Deserialize, ToSchema, Debug)]
 pub struct CreateJobInput {
+    /// URL of the file, not set when the file is an object of an S3 source
     #[schema(
         example = "http://yablufc.ddns.net:7878/piece/baga6ea4seaqb4lqf6fzjomlnhn3jahwxg52ewgcbjelzyflqjjuc7by224hbwla"
     )]
-    pub url: String,
+    pub url: Option<String>,
+    /// Further URLs of the same provider, e.g. other pieces, each worker downloads a range of one
+    /// of the URLs so that the provider can't serve every worker from its cache (up to 39 URLs)
+    #[schema(example = json!(["http://yablufc.ddns.net:7878/piece/baga6ea4seaqb4lqf6fzjomlnhn3jahwxg52ewgcbjelzyflqjjuc7by224hbwla"]))]
+    pub urls: Option<Vec<String>>,
     #[schema(example = "us_east")]
     pub routing_key: String,
     #[schema(minimum = 1, maximum = 40)]
@@ -34,8 +45,110 @@ pub struct CreateJobInput {
     pub note: Option<String>,
     #[schema(minimum = 10, maximum = 1024)]
     pub size_mb: Option<i64>,
+    /// Seed of the random range of download jobs, the same seed picks the same range of an
+    /// unchanged file (a random seed is stored in the job when not set), at most 2^53 - 1 so
+    /// JSON clients read it exactly
+    #[schema(example = 42)]
+    pub seed: Option<u64>,
+    /// Start of the range of download jobs instead of a random one, the range covers `size_mb`
+    /// from it
+    #[schema(minimum = 0)]
+    pub start_range: Option<i64>,
     #[schema(minimum = 100, maximum = 1000)]
     pub log_interval_ms: Option<i64>,
+    /// Number of parallel range requests each worker splits its range into
+    #[schema(minimum = 1, maximum = 16)]
+    pub streams_per_worker: Option<i64>,
+    #[schema(example = "Download")]
+    pub job_type: Option<JobType>,
+    /// HTTP method used to send the generated payload in upload jobs
+    #[schema(value_type = Option<String>, example = "Put")]
+    pub upload_method: Option<UploadMethod>,
+    /// Maximum duration of the download or upload in each benchmark subjob
+    #[schema(minimum = 10, maximum = 600)]
+    pub max_duration_secs: Option<i64>,
+    /// Time between the start of a benchmark subjob and the download, used for the HEAD and ping checks
+    #[schema(minimum = 5, maximum = 60)]
+    pub download_delay_secs: Option<i64>,
+    /// Time given to the workers to receive the benchmark subjob before it starts
+    #[schema(minimum = 1, maximum = 30)]
+    pub sync_delay_secs: Option<i64>,
+    /// Time given to the scaling subjob to bring the workers online
+    #[schema(minimum = 60, maximum = 7200)]
+    pub scaling_deadline_secs: Option<i64>,
+    /// Time after which the scaled up workers are descaled, must be longer than the scaling deadline
+    /// (defaults to 5 minutes after it), soak jobs must also outlast the soak (defaults to 5 minutes
+    /// after it and may exceed the maximum)
+    #[schema(minimum = 600, maximum = 7800)]
+    pub descale_deadline_secs: Option<i64>,
+    /// Latency measurement method, ICMP falling back to TCP connect when not set
+    #[schema(value_type = Option<String>, example = "Tcp")]
+    pub ping_method: Option<PingMethod>,
+    /// Keep probing the latency during the download to compare idle and loaded latency
+    #[schema(example = "false")]
+    pub measure_loaded_latency: Option<bool>,
+    /// Hash the downloaded range on each worker to detect servers returning different content
+    #[schema(example = "false")]
+    pub hash_content: Option<bool>,
+    /// Download the whole piece and verify it against the piece CID of the URL
+    #[schema(example = "false")]
+    pub verify_piece_cid: Option<bool>,
+    /// Part of the DAG to retrieve in retrieval jobs, `dag-scope` of the trustless gateway request
+    #[schema(value_type = Option<String>, example = "All")]
+    pub dag_scope: Option<DagScope>,
+    /// HTTP protocol of the download and HEAD requests, `Http3` is rejected as no worker can use
+    /// QUIC yet (defaults to `Http1`)
+    #[schema(value_type = Option<String>, example = "Http2")]
+    pub http_protocol: Option<HttpProtocol>,
+    /// Whether the download streams and HEAD requests share connections, when not set every
+    /// stream opens its own connection and the HEAD requests share one
+    #[schema(value_type = Option<String>, example = "Reuse")]
+    pub connection_policy: Option<ConnectionPolicy>,
+    /// Request headers sent to the target, `User-Agent` and `Accept` replace the defaults
+    #[schema(value_type = Option<Object>, example = json!({"User-Agent": "bms/1.1"}))]
+    pub headers: Option<RequestHeaders>,
+    /// Stored credential sent to the target as a request header, see `POST /credentials`
+    pub credential_id: Option<Uuid>,
+    /// Object of an S3 compatible storage downloaded instead of the URL, the requests are signed
+    /// with the S3 credential referenced by `credential_id`
+    pub s3_source: Option<S3Source>,
+    /// iperf3 server whose raw TCP throughput is measured after the benchmark subjobs, `host` or
+    /// `host:port` (port defaults to 5201)
+    #[schema(example = "iperf.example.com:5201")]
+    pub iperf3_server: Option<String>,
+    /// Number of iperf3 server instances on consecutive ports from the port of the server, an
+    /// instance runs one test at a time so a worker is sent to each (defaults to 1)
+    #[schema(minimum = 1, maximum = 40)]
+    pub iperf3_port_count: Option<i64>,
+    /// Size of the range requests at random offsets in random access and load test jobs
+    #[schema(minimum = 4, maximum = 1024)]
+    pub read_size_kib: Option<i64>,
+    /// Aggregate request rates of all workers in load test jobs, a benchmark subjob runs at each
+    /// rate from the lowest to the highest (1-10000 requests per second, up to 10 rates)
+    #[schema(example = json!([50, 100, 200, 400]))]
+    pub target_rps: Option<Vec<i64>>,
+    /// Replace the fixed benchmark subjobs with stages doubling the workers until the aggregate
+    /// download speed stops growing
+    #[schema(example = "false")]
+    pub saturation_search: Option<bool>,
+    /// Minimum gain of the aggregate download speed over the previous stage for the saturation
+    /// search to add more workers (defaults to 10%)
+    #[schema(minimum = 1, maximum = 100)]
+    pub saturation_gain_percent: Option<i64>,
+    /// Pick the sample size from the throughput of a pilot subjob instead of `size_mb`, which is
+    /// kept when the pilot measures nothing
+    #[schema(example = "false")]
+    pub auto_size: Option<bool>,
+    /// Download duration the automatic sample size aims for on each worker (defaults to 20 s)
+    #[schema(minimum = 5, maximum = 600)]
+    pub target_duration_secs: Option<i64>,
+    /// Duration of the continuous download in soak jobs (defaults to 1 hour)
+    #[schema(minimum = 300, maximum = 21600)]
+    pub soak_duration_secs: Option<i64>,
+    /// Length of the sampling windows in soak jobs, raised to keep at most 720 windows (defaults to
+    /// 30 s)
+    #[schema(minimum = 10, maximum = 600)]
+    pub sample_interval_secs: Option<i64>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -48,19 +161,71 @@ pub struct CreateJobResponse {
 #[derive(Debug)]
 struct CreateJobParams {
     pub url: Url,
+    pub urls: Vec<Url>,
     pub routing_key: String,
     pub worker_count: i64,
     pub entity: Option<String>,
     pub note: Option<String>,
     pub size_mb: i64,
+    pub seed: Option<u64>,
+    pub start_range: Option<i64>,
     pub log_interval_ms: i64,
+    pub streams_per_worker: i64,
+    pub job_type: JobType,
+    pub upload_method: Option<UploadMethod>,
+    pub max_duration_secs: i64,
+    pub download_delay_secs: i64,
+    pub sync_delay_secs: i64,
+    pub scaling_deadline_secs: i64,
+    pub descale_deadline_secs: i64,
+    pub ping_method: Option<PingMethod>,
+    pub measure_loaded_latency: bool,
+    pub hash_content: bool,
+    pub verify_piece_cid: bool,
+    pub dag_scope: Option<DagScope>,
+    pub http_protocol: Option<HttpProtocol>,
+    pub connection_policy: Option<ConnectionPolicy>,
+    pub headers: Option<RequestHeaders>,
+    pub credential_id: Option<Uuid>,
+    pub s3_source: Option<S3Source>,
+    pub iperf3_server: Option<Iperf3Server>,
+    pub read_size_kib: Option<i64>,
+    pub target_rps: Option<Vec<i64>>,
+    pub saturation_search: bool,
+    pub saturation_gain_percent: Option<i64>,
+    pub auto_size: bool,
+    pub target_duration_secs: Option<i64>,
+    pub soak_duration_secs: Option<i64>,
+    pub sample_interval_secs: Option<i64>,
 }
 
+// Keeps the time series of long soaks downsampled
+const MAX_SOAK_WINDOWS: i64 = 720;
+// Together with the URL of the job, one URL for each worker
+const MAX_EXTRA_URLS: usize = 39;
+// Largest integer a JSON number holds exactly in JavaScript clients
+const MAX_SEED: u64 = (1 << 53) - 1;
+
 impl TryFrom<CreateJobInput> for CreateJobParams {
     type Error = ApiResponse<()>;
 
     fn try_from(input: CreateJobInput) -> Result<Self, Self::Error> {
-        let url = Url::parse(&input.url).map_err(|_| bad_request("Invalid URL provided"))?;
+        let url = match (&input.url, &input.s3_source) {
+            (Some(url), None) => {
+                Url::parse(url).map_err(|_| bad_request("Invalid URL provided"))?
+            }
+            (None, Some(source)) => {
+                if source.region.is_empty() || source.bucket.is_empty() || source.key.is_empty() {
+                    return Err(bad_request("S3 source requires a region, bucket and key"));
+                }
+                if input.credential_id.is_none() {
+                    return Err(bad_request("S3 source requires an S3 credential"));
+                }
+                object_url(&source.endpoint, &source.bucket, &source.key)
+                    .map_err(|_| bad_request("Invalid S3 endpoint provided"))?
+            }
+            _ => return Err(bad_request("Either URL or S3 source must be provided")),
+        };
         if url.scheme() != "http" && url.scheme() != "https" {
             return Err(bad_request("URL scheme must be http or https"));
         }
@@ -69,18 +234,343 @@ impl TryFrom<CreateJobInput> for CreateJobParams {
             return Err(bad_request("Routing key cannot be empty"));
         }
 
+        // The workers can't use QUIC, HTTP/3 would silently be measured over HTTP/2
+        if input.http_protocol == Some(HttpProtocol::Http3) {
+            return Err(bad_request("HTTP/3 is not supported by the workers"));
+        }
+
+        let job_type = input.job_type.unwrap_or_default();
+        if input.s3_source.is_some()
+            && !matches!(
+                job_type,
+                JobType::Download | JobType::RandomAccess | JobType::LoadTest | JobType::Soak
+            )
+        {
+            return Err(bad_request(
+                "S3 source requires the Download, RandomAccess, LoadTest or Soak job type",
+            ));
+        }
+        let upload_method = match job_type {
+            JobType::Upload => Some(input.upload_method.unwrap_or_default()),
+            _ if input.upload_method.is_some() => {
+                return Err(bad_request("Upload method requires the Upload job type"));
+            }
+            _ => None,
+        };
+
+        let dag_scope = match job_type {
+            JobType::Retrieval => {
+                if !url.path().starts_with("/ipfs/") {
+                    return Err(bad_request(
+                        "Retrieval requires a trustless gateway URL with /ipfs/<cid> path",
+                    ));
+                }
+                Some(input.dag_scope.unwrap_or_default())
+            }
+            _ if input.dag_scope.is_some() => {
+                return Err(bad_request("DAG scope requires the Retrieval job type"));
+            }
+            _ => None,
+        };
+
+        let verify_piece_cid = input.verify_piece_cid.unwrap_or(false);
+        if verify_piece_cid {
+            if job_type != JobType::Download {
+                return Err(bad_request(
+                    "Piece CID verification requires the Download job type",
+                ));
+            }
+            if input.streams_per_worker.is_some_and(|streams| streams > 1) {
+                return Err(bad_request(
+                    "Piece CID verification requires a single stream per worker",
+                ));
+            }
+            if !url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .is_some_and(|segment| segment.starts_with("baga"))
+            {
+                return Err(bad_request(
+                    "Piece CID verification requires a URL ending with a piece CID",
+                ));
+            }
+        }
+
+        // The range and the connection are managed by the workers
+        const RESERVED_HEADERS: [&str; 4] = ["host", "range", "content-length", "connection"];
+        for (name, value) in input.headers.iter().flat_map(|headers| headers.0.iter()) {
+            let name = reqwest::header::HeaderName::try_from(name)
+                .map_err(|_| bad_request(format!("Invalid header name: {name}")))?;
+            if RESERVED_HEADERS.contains(&name.as_str()) {
+                return Err(bad_request(format!("Header {name} cannot be set")));
+            }
+            reqwest::header::HeaderValue::try_from(value)
+                .map_err(|_| bad_request(format!("Invalid value of header {name}")))?;
+        }
+
+        let read_size_kib = match job_type {
+            JobType::RandomAccess | JobType::LoadTest => {
+                Some(input.read_size_kib.unwrap_or(64).clamp(4, 1024)) // Default 64 KiB, Possible size 4-1024 KiB
+            }
+            _ if input.read_size_kib.is_some() => {
+                return Err(bad_request(
+                    "Read size requires the RandomAccess or LoadTest job type",
+                ));
+            }
+            _ => None,
+        };
+
+        let target_rps = match (job_type, input.target_rps) {
+            (JobType::LoadTest, Some(mut target_rps)) => {
+                if target_rps.is_empty() || target_rps.len() > 10 {
+                    return Err(bad_request("Load test requires 1 to 10 target rates"));
+                }
+                if target_rps.iter().any(|rps| !(1..=10_000).contains(rps)) {
+                    return Err(bad_request(
+                        "Target rates must be between 1 and 10000 requests per second",
+                    ));
+                }
+                // The steps ramp up so the rate where the errors start is found on the way
+                target_rps.sort_unstable();
+                target_rps.dedup();
+                Some(target_rps)
+            }
+            (JobType::LoadTest, None) => {
+                return Err(bad_request("Load test requires target rates"));
+            }
+            (_, Some(_)) => {
+                return Err(bad_request("Target rates require the LoadTest job type"));
+            }
+            (_, None) => None,
+        };
+
+        let saturation_search = input.saturation_search.unwrap_or(false);
+        if saturation_search && job_type != JobType::Download {
+            return Err(bad_request(
+                "Saturation search requires the Download job type",
+            ));
+        }
+        let saturation_gain_percent = match input.saturation_gain_percent {
+            Some(_) if !saturation_search => {
+                return Err(bad_request(
+                    "Saturation gain requires the saturation search",
+                ));
+            }
+            Some(gain_percent) => Some(gain_percent.clamp(1, 100)), // Possible gain 1-100%
+            None => None,
+        };
+
+        let auto_size = input.auto_size.unwrap_or(false);
+        if auto_size && (job_type != JobType::Download || verify_piece_cid) {
+            return Err(bad_request(
+                "Automatic sample size requires the Download job type without piece CID verification",
+            ));
+        }
+        let urls = match input.urls {
+            Some(urls) if !urls.is_empty() => {
+                if job_type != JobType::Download
+                    || input.s3_source.is_some()
+                    || verify_piece_cid
+                    || auto_size
+                    || saturation_search
+                {
+                    return Err(bad_request(
+                        "Multiple URLs require the Download job type without S3 source, piece CID verification, automatic sample size or saturation search",
+                    ));
+                }
+                if urls.len() > MAX_EXTRA_URLS {
+                    return Err(bad_request(format!(
+                        "At most {MAX_EXTRA_URLS} further URLs can be provided"
+                    )));
+                }
+                let mut parsed_urls: Vec<Url> = Vec::with_capacity(urls.len());
+                for extra_url in &urls {
+                    let extra_url = Url::parse(extra_url)
+                        .map_err(|_| bad_request(format!("Invalid URL provided: {extra_url}")))?;
+                    if extra_url.scheme() != "http" && extra_url.scheme() != "https" {
+                        return Err(bad_request("URL scheme must be http or https"));
+                    }
+                    if extra_url == url || parsed_urls.contains(&extra_url) {
+                        return Err(bad_request(format!("URL provided twice: {extra_url}")));
+                    }
+                    parsed_urls.push(extra_url);
+                }
+                parsed_urls
+            }
+            _ => vec![],
+        };
+
+        if input.seed.is_some() || input.start_range.is_some() {
+            if job_type != JobType::Download || verify_piece_cid {
+                return Err(bad_request(
+                    "Seed and start range require the Download job type without piece CID verification",
+                ));
+            }
+            if input.seed.is_some() && input.start_range.is_some() {
+                return Err(bad_request("Either seed or start range can be provided"));
+            }
+        }
+        if input.seed.is_some_and(|seed| seed > MAX_SEED) {
+            return Err(bad_request(format!(
+                "Seed cannot be larger than {MAX_SEED}"
+            )));
+        }
+        if let Some(start_range) = input.start_range {
+            if start_range < 0 {
+                return Err(bad_request("Start range cannot be negative"));
+            }
+            // The pilot and the further URLs pick ranges of other sizes or files
+            if auto_size || !urls.is_empty() {
+                return Err(bad_request(
+                    "Start range cannot be used with the automatic sample size or multiple URLs, use a seed",
+                ));
+            }
+        }
+
+        let max_duration_secs = input.max_duration_secs.unwrap_or(60).clamp(10, 600); // Default 60 s, Possible range 10-600 s
+        let target_duration_secs = match input.target_duration_secs {
+            Some(_) if !auto_size => {
+                return Err(bad_request(
+                    "Target duration requires the automatic sample size",
+                ));
+            }
+            // Default 20 s, Possible range 5 s - maximum duration
+            Some(target_duration_secs) => Some(target_duration_secs.clamp(5, max_duration_secs)),
+            None if auto_size => Some(20.min(max_duration_secs)),
+            None => None,
+        };
+
+        let (soak_duration_secs, sample_interval_secs) = match job_type {
+            JobType::Soak => {
+                let soak_duration_secs = input.soak_duration_secs.unwrap_or(3600).clamp(300, 21600); // Default 1 hour, Possible range 5 minutes - 6 hours
+                let sample_interval_secs = input
+                    .sample_interval_secs
+                    .unwrap_or(30)
+                    .clamp(10, 600) // Default 30 s, Possible range 10-600 s
+                    .max((soak_duration_secs + MAX_SOAK_WINDOWS - 1) / MAX_SOAK_WINDOWS);
+                (Some(soak_duration_secs), Some(sample_interval_secs))
+            }
+            _ if input.soak_duration_secs.is_some() || input.sample_interval_secs.is_some() => {
+                return Err(bad_request(
+                    "Soak duration and sample interval require the Soak job type",
+                ));
+            }
+            _ => (None, None),
+        };
+
+        let iperf3_server = match &input.iperf3_server {
+            Some(server) => Some(parse_iperf3_server(server, input.iperf3_port_count)?),
+            None if input.iperf3_port_count.is_some() => {
+                return Err(bad_request("iperf3 port count requires an iperf3 server"));
+            }
+            None => None,
+        };
+
+        let scaling_deadline_secs = input.scaling_deadline_secs.unwrap_or(1500).clamp(60, 7200); // Default 25 minutes, Possible range 1 minute - 2 hours
+
+        // Default 5 minutes after the scaling deadline, Possible range 10 minutes - 2 hours 10 minutes
+        let descale_deadline_secs = input
+            .descale_deadline_secs
+            .unwrap_or(scaling_deadline_secs + 300)
+            .clamp(600, 7800);
+        if descale_deadline_secs <= scaling_deadline_secs {
+            return Err(bad_request(
+                "Descale deadline must be longer than the scaling deadline",
+            ));
+        }
+        // The checks stop 2 s before the download, at least 3 s are left for them
+        let download_delay_secs = input.download_delay_secs.unwrap_or(10).clamp(5, 60); // Default 10 s, Possible range 5-60 s
+        let sync_delay_secs = input.sync_delay_secs.unwrap_or(1).clamp(1, 30); // Default 1 s, Possible range 1-30 s
+
+        // The workers are descaled from the start of the scaling, a soak must end before that
+        let descale_deadline_secs = match soak_duration_secs {
+            Some(soak_duration_secs) => {
+                let soak_end_secs = scaling_deadline_secs
+                    + sync_delay_secs
+                    + download_delay_secs
+                    + soak_duration_secs;
+                match input.descale_deadline_secs {
+                    Some(secs) if secs <= soak_end_secs => {
+                        return Err(bad_request(
+                            "Descale deadline must be longer than the scaling deadline and the soak",
+                        ));
+                    }
+                    Some(secs) => secs.min(soak_end_secs + 7800),
+                    None => soak_end_secs + 300,
+                }
+            }
+            None => descale_deadline_secs,
+        };
+
         Ok(CreateJobParams {
             url,
+            urls,
             routing_key: input.routing_key,
             worker_count: input.worker_count.unwrap_or(10).clamp(1, 40),
             entity: input.entity,
             note: input.note,
             size_mb: input.size_mb.unwrap_or(100).clamp(10, 1024), // Default 100 MB, Possible size 10-1024 MB
+            seed: input.seed,
+            start_range: input.start_range,
             log_interval_ms: input.log_interval_ms.unwrap_or(1000).clamp(100, 1000), // Default 1000 ms, Possible range 100-1000 ms
+            streams_per_worker: input.streams_per_worker.unwrap_or(1).clamp(1, 16), // Default 1 stream, Possible range 1-16 streams
+            job_type,
+            upload_method,
+            max_duration_secs,
+            download_delay_secs,
+            sync_delay_secs,
+            scaling_deadline_secs,
+            descale_deadline_secs,
+            ping_method: input.ping_method,
+            measure_loaded_latency: input.measure_loaded_latency.unwrap_or(false),
+            hash_content: input.hash_content.unwrap_or(false),
+            verify_piece_cid,
+            dag_scope,
+            http_protocol: input.http_protocol,
+            connection_policy: input.connection_policy,
+            headers: input.headers.filter(|headers| !headers.is_empty()),
+            credential_id: input.credential_id,
+            s3_source: input.s3_source,
+            iperf3_server,
+            read_size_kib,
+            target_rps,
+            saturation_search,
+            saturation_gain_percent,
+            auto_size,
+            target_duration_secs,
+            soak_duration_secs,
+            sample_interval_secs,
         })
     }
 }
 
+/// Parse the `host` or `host:port` of the iperf3 server
+fn parse_iperf3_server(
+    server: &str,
+    port_count: Option<i64>,
+) -> Result<Iperf3Server, ApiResponse<()>> {
+    const DEFAULT_PORT: u16 = 5201;
+
+    let url = Url::parse(&format!("iperf3://{server}"))
+        .ok()
+        .filter(|url| url.path().is_empty() && url.query().is_none() && url.username().is_empty())
+        .ok_or_else(|| bad_request("Invalid iperf3 server provided"))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| bad_request("Invalid iperf3 server provided"))?;
+    let port = url.port().unwrap_or(DEFAULT_PORT);
+    let port_count = port_count.unwrap_or(1).clamp(1, 40) as u16; // Default 1 instance, Possible range 1-40 instances
+    if port.checked_add(port_count - 1).is_none() {
+        return Err(bad_request("iperf3 ports exceed the port range"));
+    }
+
+    Ok(Iperf3Server {
+        host: host.to_string(),
+        port,
+        port_count,
+    })
+}
+
 /// Creates a new Job to be processed by the worker
 #[utoipa::path(
     post,
@@ -95,6 +585,32 @@ The Job consists of three subjobs:
 - **Benchmark SubJob 2**: Performs the second part of the benchmark work.
 
 **All subjobs are carried out sequentially.**
+
+With `job_type` set to `Upload` the benchmark subjobs send a generated payload of `size_mb` to the URL instead of downloading a range of it.
+
+With `job_type` set to `Retrieval` the benchmark subjobs fetch a CAR of the CID in the URL from an IPFS trustless gateway and verify every block.
+
+With `job_type` set to `RandomAccess` the benchmark subjobs send range requests of `read_size_kib` at random offsets of the file for `max_duration_secs`, `streams_per_worker` requests at a time, and record the time to first byte of each. The job summary profiles the seek latency.
+
+With `job_type` set to `LoadTest` a benchmark subjob runs at each of the `target_rps` instead, the workers share the rate and send range requests of `read_size_kib` at random offsets of the file on schedule for `max_duration_secs`, whether or not the earlier ones completed. The job summary aggregates the success rate, status codes and latency percentiles of each rate into a rate-latency curve and reports the first rate where the requests start failing.
+
+With `job_type` set to `Soak` a single **Soak SubJob** downloads the file over and over with every worker for `soak_duration_secs`, sampling the throughput in windows of `sample_interval_secs` that start together on every worker. The job summary reports the aggregate time series, the burst and sustained bandwidth, and the step where the throughput dropped when the provider throttles.
+
+With `saturation_search` the benchmark subjobs are replaced by **SaturationSearch SubJobs** downloading with 1, 2, 4, ... workers, each stage created once the previous one completed. The search stops when the aggregate download speed grows less than `saturation_gain_percent` over the previous stage or every worker was used. The job summary reports the knee point and the maximum sustainable bandwidth.
+
+With `auto_size` a **Pilot SubJob** downloads with every worker for a few seconds before the benchmark subjobs, and `size_mb` of the job is set so each worker downloads for about `target_duration_secs` at the measured speed. The pilot reads the first half of the picked range and the benchmark range is taken from the second half, so no benchmark byte was cached by the pilot.
+
+The random range of download jobs is picked from `seed`, a random seed when not set, which is stored in the job so that the same range of an unchanged file can be picked again. `start_range` sets the start of the range instead, and `POST /jobs/{job_id}/rerun` repeats a past job on the same ranges.
+
+With `urls` next to `url` every URL gets its own random range of `size_mb` and the workers of each benchmark subjob are assigned to the URLs in turn, the assignment continuing from the previous subjob. The job summary groups the download speed by URL, a URL is read cold in the first subjob downloading it and cached in the later ones.
+
+With `verify_piece_cid` the workers download the whole piece instead of a range of `size_mb` and verify it against the piece CID of the URL.
+
+Targets requiring authentication are reached with custom `headers` or a stored credential referenced by `credential_id`, the header values are stored encrypted like the credentials and redacted from the job responses.
+
+With `s3_source` instead of `url` the workers download a range of an object in an S3 compatible storage (AWS S3, MinIO), every request signed with SigV4 using the S3 credential referenced by `credential_id`.
+
+With `iperf3_server` an **iperf3 SubJob** follows the benchmark subjobs, the workers receive data from the iperf3 server in reverse mode with `streams_per_worker` parallel streams for `max_duration_secs` to measure the raw TCP throughput next to the HTTP results.
     "#,
     responses(
         (status = 200, description = "Job Created", body = CreateJobResponse),
@@ -117,13 +633,113 @@ pub async fn handle_create_job(
     let params: CreateJobParams = payload.try_into()?;
     let target_worker_count = params.worker_count;
 
+    // The header values may hold secrets, they are stored encrypted like the credentials
+    let encrypted_headers = params
+        .headers
+        .as_ref()
+        .map(encrypt_headers)
+        .transpose()
+        .map_err(|e| bad_request(format!("Failed to encrypt the headers: {e}")))?;
+
+    // The size of the file is checked with the same headers the workers send
+    let (headers, s3_signing) = request_auth(
+        &state.repo.credential,
+        encrypted_headers.as_ref(),
+        params.credential_id.as_ref(),
+        params.s3_source.as_ref(),
+    )
+    .await
+    .map_err(|e| bad_request(format!("Failed to get the credential: {e}")))?;
+
+    // Random ranges are picked from a stored seed, so the job can be repeated on the same bytes
+    let seed = match params.job_type {
+        JobType::Download if !params.verify_piece_cid && params.start_range.is_none() => Some(
+            params
+                .seed
+                .unwrap_or_else(|| rand::thread_rng().gen_range(0..=MAX_SEED)),
+        ),
+        _ => None,
+    };
+    // Unlike StdRng the algorithm is fixed, a stored seed picks the same range after upgrades
+    let mut rng = ChaCha8Rng::seed_from_u64(seed.unwrap_or_default());
+
     // Create the job
-    let (start_range, end_range) =
-        get_file_range_for_file(params.url.as_ref(), &params.size_mb).await?;
+    let (start_range, end_range) = match params.job_type {
+        // The piece CID covers the whole piece, so all of it is downloaded
+        JobType::Download if params.verify_piece_cid => (
+            0,
+            get_content_length(&params.url, &headers, s3_signing.as_ref()).await? - 1,
+        ),
+        // The pilot downloads from the first half of the largest range, the benchmark range is
+        // cut from the second half
+        JobType::Download if params.auto_size => {
+            let content_length =
+                get_content_length(&params.url, &headers, s3_signing.as_ref()).await?;
+            let max_size_mb = ((content_length - 1) / (1024 * 1024)).min(1024);
+            if max_size_mb < 10 {
+                return Err(bad_request("File size is less than 10 MB"));
+            }
+            random_range(content_length, &max_size_mb, &mut rng)?
+        }
+        JobType::Download => match params.start_range {
+            Some(start_range) => {
+                let content_length =
+                    get_content_length(&params.url, &headers, s3_signing.as_ref()).await?;
+                fixed_range(content_length, start_range, &params.size_mb)?
+            }
+            None => {
+                get_file_range_for_file(
+                    &params.url,
+                    &params.size_mb,
+                    &headers,
+                    s3_signing.as_ref(),
+                    &mut rng,
+                )
+                .await?
+            }
+        },
+        // Upload jobs have no file to pick a range from, the range describes the payload size
+        JobType::Upload => (0, params.size_mb * 1024 * 1024 - 1),
+        // Retrieval jobs fetch the whole DAG of the CID, there is no range
+        JobType::Retrieval => (0, 0),
+        // Random access and load test jobs read anywhere in the file, soak jobs download the
+        // whole file over and over
+        JobType::RandomAccess | JobType::LoadTest | JobType::Soak => (
+            0,
+            get_content_length(&params.url, &headers, s3_signing.as_ref()).await? - 1,
+        ),
+    };
+
+    // Every URL of a multi-URL job gets its own random range of the sample size
+    let sources = if params.urls.is_empty() {
+        None
+    } else {
+        let mut sources = vec![RangeAssignment {
+            url: params.url.to_string(),
+            start_range,
+            end_range,
+        }];
+        for url in &params.urls {
+            let (start_range, end_range) = get_file_range_for_file(
+                url,
+                &params.size_mb,
+                &headers,
+                s3_signing.as_ref(),
+                &mut rng,
+            )
+            .await?;
+            sources.push(RangeAssignment {
+                url: url.to_string(),
+                start_range,
+                end_range,
+            });
+        }
+        Some(sources)
+    };
 
     let job_id = Uuid::new_v4();
 
-    let job = state
+    let mut job = state
         .repo
         .job
         .create_job(
@@ -131,40 +747,58 @@ pub async fn handle_create_job(
             params.url.to_string(),
             &params.routing_key,
             JobStatus::Pending,
-            JobDetails::new(
+            JobDetails {
                 start_range,
                 end_range,
-                target_worker_count,
-                params.entity.clone(),
-                params.note.clone(),
-                params.log_interval_ms,
-                params.size_mb,
-            ),
+                target_worker_count: Some(target_worker_count),
+                entity: params.entity.clone(),
+                note: params.note.clone(),
+                log_interval_ms: params.log_interval_ms,
+                size_mb: if params.verify_piece_cid {
+                    (end_range - start_range) / (1024 * 1024) + 1 // Piece size rounded up to MB
+                } else {
+                    params.size_mb
+                },
+                streams_per_worker: Some(params.streams_per_worker),
+                job_type: params.job_type,
+                upload_method: params.upload_method,
+                max_duration_secs: Some(params.max_duration_secs),
+                download_delay_secs: Some(params.download_delay_secs),
+                sync_delay_secs: Some(params.sync_delay_secs),
+                scaling_deadline_secs: Some(params.scaling_deadline_secs),
+                descale_deadline_secs: Some(params.descale_deadline_secs),
+                ping_method: params.ping_method,
+                measure_loaded_latency: Some(params.measure_loaded_latency),
+                hash_content: Some(params.hash_content),
+                verify_piece_cid: Some(params.verify_piece_cid),
+                dag_scope: params.dag_scope,
+                http_protocol: params.http_protocol,
+                connection_policy: params.connection_policy,
+                headers: encrypted_headers,
+                credential_id: params.credential_id,
+                s3_source: params.s3_source.clone(),
+                iperf3_server: params.iperf3_server.clone(),
+                read_size_kib: params.read_size_kib,
+                target_rps: params.target_rps.clone(),
+                saturation_search: Some(params.saturation_search),
+                saturation_gain_percent: params.saturation_gain_percent,
+                auto_size: Some(params.auto_size),
+                target_duration_secs: params.target_duration_secs,
+                soak_duration_secs: params.soak_duration_secs,
+                sample_interval_secs: params.sample_interval_secs,
+                sources,
+                seed,
+                ..Default::default()
+            },
         )
         .await
         .map_err(|_| internal_server_error("Failed to create job"))?;
 
-    debug!("Job created successfully: {:?}", job);
+    job.details.redact_headers();
 
-    let scaling_sub_job = state
-        .repo
-        .sub_job
-        .create_sub_job(
-            Uuid::new_v4(),
-            job.id,
-            SubJobStatus::Created,
-            SubJobType::Scaling,
-            SubJobDetails::topic(job.routing_key.clone()),
-        )
-        .await
-        .map_err(|_| internal_server_error("Failed to create scaling sub job"))?;
+    debug!("Job created successfully: {:?}", job);
 
-    let sub_jobs = vec![
-        scaling_sub_job,
-        create_sub_job(&state, &job, SubJobDetails::partial(1)).await?,
-        create_sub_job(&state, &job, SubJobDetails::partial(80)).await?,
-        create_sub_job(&state, &job, SubJobDetails::empty()).await?,
-    ];
+    let sub_jobs = create_sub_jobs(&state, &job).await?;
 
     debug!(
         "Job with sub jobs created successfully: {}, sub_jobs: {:?}",
@@ -175,9 +809,71 @@ pub async fn handle_create_job(
 }
 
 /// Get a random range of 100MB from the file using HEAD request
-async fn get_file_range_for_file(url: &str, size_mb: &i64) -> Result<(i64, i64), ApiResponse<()>> {
-    let response = Client::new()
-        .head(url)
+async fn get_file_range_for_file(
+    url: &Url,
+    size_mb: &i64,
+    headers: &RequestHeaders,
+    s3_signing: Option<&S3Signing>,
+    rng: &mut ChaCha8Rng,
+) -> Result<(i64, i64), ApiResponse<()>> {
+    let content_length = get_content_length(url, headers, s3_signing).await?;
+
+    random_range(content_length, size_mb, rng)
+}
+
+/// Pick a random range of the size in the file
+fn random_range(
+    content_length: i64,
+    size_mb: &i64,
+    rng: &mut ChaCha8Rng,
+) -> Result<(i64, i64), ApiResponse<()>> {
+    let size = size_mb * 1024 * 1024;
+
+    if content_length <= size {
+        return Err(bad_request(format!("File size is less than {size_mb} MB")));
+    }
+
+    let start_range = rng.gen_range(0..content_length - size);
+    let end_range = start_range + size;
+
+    debug!("Selected range: {} - {}", start_range, end_range);
+
+    Ok((start_range, end_range))
+}
+
+/// Range of the size from the given start, which must leave the size in the file
+fn fixed_range(
+    content_length: i64,
+    start_range: i64,
+    size_mb: &i64,
+) -> Result<(i64, i64), ApiResponse<()>> {
+    let size = size_mb * 1024 * 1024;
+
+    if start_range >= content_length - size {
+        return Err(bad_request(format!(
+            "File size is less than {size_mb} MB after the start range"
+        )));
+    }
+
+    Ok((start_range, start_range + size))
+}
+
+/// Get the size of the file using HEAD request, signed when the file is in an S3 source
+async fn get_content_length(
+    url: &Url,
+    headers: &RequestHeaders,
+    s3_signing: Option<&S3Signing>,
+) -> Result<i64, ApiResponse<()>> {
+    let mut request = Client::new().head(url.clone());
+    for (name, value) in &headers.0 {
+        request = request.header(name, value);
+    }
+    if let Some(s3_signing) = s3_signing {
+        for (name, value) in s3_signing.signed_headers("HEAD", url) {
+            request = request.header(name, value);
+        }
+    }
+    let response = request
         .send()
         .await
         .map_err(|e| bad_request(format!("Failed to execute HEAD request {e}")))?;
@@ -196,24 +892,82 @@ async fn get_file_range_for_file(url: &str, size_mb: &i64) -> Result<(i64, i64),
 
     debug!("Content-Length: {:?}", content_length);
 
-    let size = size_mb * 1024 * 1024;
-
-    if content_length < size {
-        return Err(bad_request(format!("File size is less than {size_mb} MB")));
+    if content_length <= 0 {
+        return Err(bad_request("File is empty"));
     }
 
-    let mut rng = rand::thread_rng();
-    let start_range = rng.gen_range(0..content_length - size);
-    let end_range = start_range + size;
+    Ok(content_length)
+}
 
-    debug!("Selected range: {} - {}", start_range, end_range);
+/// Create the subjobs of the job, the scaling subjob followed by the benchmark subjobs of its
+/// details
+pub(super) async fn create_sub_jobs(
+    state: &Arc<AppState>,
+    job: &Job,
+) -> Result<Vec<SubJob>, ApiResponse<()>> {
+    let saturation_search = job.details.saturation_search.unwrap_or(false);
 
-    Ok((start_range, end_range))
+    let scaling_sub_job = state
+        .repo
+        .sub_job
+        .create_sub_job(
+            Uuid::new_v4(),
+            job.id,
+            SubJobStatus::Created,
+            SubJobType::Scaling,
+            SubJobDetails::topic(job.routing_key.clone()),
+        )
+        .await
+        .map_err(|_| internal_server_error("Failed to create scaling sub job"))?;
+
+    let sub_job_type = match job.details.job_type {
+        // Every stage of the saturation search is created when the previous one completes
+        JobType::Download if saturation_search => SubJobType::SaturationSearch,
+        JobType::Download => SubJobType::CombinedDHP,
+        JobType::Upload => SubJobType::Upload,
+        JobType::Retrieval => SubJobType::Retrieval,
+        JobType::RandomAccess => SubJobType::RandomAccess,
+        JobType::LoadTest => SubJobType::LoadTest,
+        JobType::Soak => SubJobType::Soak,
+    };
+
+    // Load tests step through the target rates with all workers
+    let benchmark_details = match &job.details.target_rps {
+        Some(target_rps) => target_rps
+            .iter()
+            .map(|rps| SubJobDetails::load_test(*rps))
+            .collect(),
+        None if saturation_search => vec![SubJobDetails::saturation_stage(1, None)],
+        // A single soak with every worker, its windows show the throughput over time
+        None if job.details.job_type == JobType::Soak => vec![SubJobDetails::empty()],
+        None => vec![
+            SubJobDetails::partial(1),
+            SubJobDetails::partial(80),
+            SubJobDetails::empty(),
+        ],
+    };
+
+    let mut sub_jobs = vec![scaling_sub_job];
+    if job.details.auto_size.unwrap_or(false) {
+        sub_jobs.push(create_sub_job(state, job, SubJobType::Pilot, SubJobDetails::empty()).await?);
+    }
+    for details in benchmark_details {
+        sub_jobs.push(create_sub_job(state, job, sub_job_type.clone(), details).await?);
+    }
+    // The raw TCP throughput is measured apart from the benchmark, both would share the network,
+    // the saturation search creates the iperf3 subjob once it finished
+    if job.details.iperf3_server.is_some() && !saturation_search {
+        sub_jobs
+            .push(create_sub_job(state, job, SubJobType::Iperf3, SubJobDetails::empty()).await?);
+    }
+
+    Ok(sub_jobs)
 }
 
 async fn create_sub_job(
     state: &Arc<AppState>,
     job: &Job,
+    sub_job_type: SubJobType,
     details: SubJobDetails,
 ) -> Result<SubJob, ApiResponse<()>> {
     let sub_job = state
@@ -223,7 +977,7 @@ async fn create_sub_job(
             Uuid::new_v4(),
             job.id,
             SubJobStatus::Created,
-            SubJobType::CombinedDHP,
+            sub_job_type,
             details,
         )
         .await
@@ -233,3 +987,19 @@ async fn create_sub_job(
 
     Ok(sub_job)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_range_is_reproducible_from_the_seed() {
+        let content_length = 32 * 1024 * 1024 * 1024;
+        let pick =
+            |seed| random_range(content_length, &100, &mut ChaCha8Rng::seed_from_u64(seed)).ok();
+
+        // A stored seed must keep picking the same range after dependency upgrades
+        assert_eq!(pick(42), Some((23_358_272_763, 23_463_130_363)));
+        assert_ne!(pick(43), pick(42));
+    }
+}
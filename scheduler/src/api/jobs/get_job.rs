@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use axum::{
     debug_handler,
@@ -7,15 +7,19 @@ use axum::{
 use axum_extra::extract::WithRejection;
 use common::api_response::*;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use tracing::{debug, error, info};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use crate::{
-    job_repository::JobWithSubJobsWithData, state::AppState, sub_job_repository::SubJobType,
+    aggregation::{aggregate_downloads, DownloadAggregates, WorkerLogs},
+    job_repository::JobWithSubJobsWithData,
+    state::AppState,
+    sub_job_repository::SubJobType,
 };
 
+use super::job_summary::{summarize_job, JobSummary, SummaryData, WorkerLatencySamples};
+
 #[derive(Deserialize, ToSchema, IntoParams)]
 pub struct GetJobPathParams {
     job_id: Uuid,
@@ -34,21 +38,6 @@ pub struct GetJobResponse {
     job: JobWithSubJobsWithData,
 }
 
-#[derive(Serialize, ToSchema)]
-pub struct DownloadSpeed {
-    sub_job_id: Uuid,
-    download_speed: f64,
-    average_time_to_first_byte_ms: f64,
-}
-
-#[derive(Serialize, ToSchema)]
-pub struct JobSummary {
-    pub max_download_speed: Option<f64>,
-    pub download_speeds: Option<Vec<DownloadSpeed>>,
-    pub average_end_latency: Option<f64>,
-    pub average_gateway_latency: Option<f64>,
-}
-
 /// Get the job with sub jobs and worker data
 #[utoipa::path(
     get,
@@ -78,7 +67,7 @@ pub async fn handle_get_job(
 
     info!("Getting data for job_id: {}", job_id);
 
-    let job = state
+    let mut job = state
         .repo
         .job
         .get_job_by_id_with_subjobs_and_data(job_id, extended)
@@ -91,59 +80,152 @@ pub async fn handle_get_job(
             }
         })?;
 
+    job.details.redact_headers();
+
     debug!("Job data found for job_id: {} {:?}", job_id, job);
 
-    let download_speeds_iter = job
+    let data = SummaryData {
+        download_aggregates: load_download_aggregates(&state, &mut job).await?,
+        latency_samples: load_latency_samples(&state, &job_id).await?,
+        load_test_samples: load_load_test_samples(&state, &job_id).await?,
+    };
+
+    Ok(ok_response(GetJobResponse {
+        summary: summarize_job(&job, data),
+        job,
+    }))
+}
+
+/// Take the download aggregates stored in the details of the completed sub jobs, the interval logs
+/// are loaded and aggregated only for the sub jobs without them
+async fn load_download_aggregates(
+    state: &AppState,
+    job: &mut JobWithSubJobsWithData,
+) -> Result<BTreeMap<Uuid, DownloadAggregates>, ApiResponse<()>> {
+    let mut download_aggregates = BTreeMap::new();
+    let mut unaggregated = Vec::new();
+    for sub_job in job
         .sub_jobs
-        .iter()
+        .iter_mut()
         .filter(|sub_job| sub_job.r#type == SubJobType::CombinedDHP)
-        .map(|sub_job| {
-            let sub_job_download_speed = sub_job.worker_data.iter().map(|wd| {
-                wd.download
-                    .get("download_speed")
-                    .unwrap_or(&json!(0.0))
-                    .as_f64()
-                    .unwrap_or(0.0)
-            });
-
-            let sub_job_ttfb = sub_job.worker_data.iter().map(|wd| {
-                wd.download
-                    .get("time_to_first_byte_ms")
-                    .unwrap_or(&json!(0.0))
-                    .as_f64()
-                    .unwrap_or(0.0)
-            });
-
-            let sub_job_ttfb_sum = sub_job_ttfb.sum::<f64>();
-            let worker_count = sub_job.worker_data.len() as f64;
-            let average_ttfb = if worker_count > 0.0 {
-                sub_job_ttfb_sum / worker_count
-            } else {
-                0.0
-            };
-
-            DownloadSpeed {
-                sub_job_id: sub_job.id,
-                download_speed: sub_job_download_speed.sum::<f64>(),
-                average_time_to_first_byte_ms: average_ttfb,
+    {
+        match sub_job
+            .details
+            .as_object_mut()
+            .and_then(|details| details.remove("download_aggregates"))
+            .and_then(|aggregates| serde_json::from_value(aggregates).ok())
+        {
+            Some(aggregates) => {
+                download_aggregates.insert(sub_job.id, aggregates);
             }
-        });
-
-    let download_speeds: Vec<DownloadSpeed> = download_speeds_iter.collect();
+            None => unaggregated.push(sub_job.id),
+        }
+    }
+    if unaggregated.is_empty() {
+        return Ok(download_aggregates);
+    }
+
+    let mut download_logs: BTreeMap<Uuid, Vec<WorkerLogs>> = BTreeMap::new();
+    for logs_row in state
+        .repo
+        .data
+        .get_download_logs_by_sub_job_ids(&unaggregated)
+        .await
+        .map_err(|e| {
+            error!("Failed to get download logs from the database: {:?}", e);
+            bad_request("Failed to get data from the database")
+        })?
+    {
+        let (Some(sub_job_id), Some(worker_logs)) = (
+            logs_row.sub_job_id,
+            logs_row.logs.and_then(|logs| {
+                WorkerLogs::parse(
+                    logs_row.worker_name.unwrap_or_default(),
+                    logs_row.download_start_time,
+                    logs,
+                )
+            }),
+        ) else {
+            continue;
+        };
+        download_logs
+            .entry(sub_job_id)
+            .or_default()
+            .push(worker_logs);
+    }
+
+    for sub_job_id in unaggregated {
+        let worker_logs = download_logs.remove(&sub_job_id).unwrap_or_default();
+        download_aggregates.insert(
+            sub_job_id,
+            aggregate_downloads(&worker_logs, job.details.log_interval_ms),
+        );
+    }
+
+    Ok(download_aggregates)
+}
 
-    let max_download_speed = download_speeds
-        .iter()
-        .map(|ds| ds.download_speed)
-        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
-        .unwrap_or(0.0);
+/// Load the ping, head and random access latency samples by worker data
+async fn load_latency_samples(
+    state: &AppState,
+    job_id: &Uuid,
+) -> Result<BTreeMap<Uuid, WorkerLatencySamples>, ApiResponse<()>> {
+    let mut latency_samples = BTreeMap::new();
+    for samples_row in state
+        .repo
+        .data
+        .get_latency_samples_by_job_id(job_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get latency samples from the database: {:?}", e);
+            bad_request("Failed to get data from the database")
+        })?
+    {
+        let parse = |samples: Option<serde_json::Value>| {
+            samples
+                .and_then(|samples| serde_json::from_value::<Vec<f64>>(samples).ok())
+                .unwrap_or_default()
+        };
+        latency_samples.insert(
+            samples_row.id,
+            WorkerLatencySamples {
+                ping: parse(samples_row.ping),
+                head: parse(samples_row.head),
+                ttfb: parse(samples_row.ttfb),
+            },
+        );
+    }
+
+    Ok(latency_samples)
+}
 
-    Ok(ok_response(GetJobResponse {
-        job,
-        summary: JobSummary {
-            max_download_speed: Some(max_download_speed),
-            download_speeds: Some(download_speeds),
-            average_end_latency: None,
-            average_gateway_latency: None,
-        },
-    }))
+/// Load the latency samples of the load test steps by sub job
+async fn load_load_test_samples(
+    state: &AppState,
+    job_id: &Uuid,
+) -> Result<BTreeMap<Uuid, Vec<f64>>, ApiResponse<()>> {
+    let mut load_test_samples: BTreeMap<Uuid, Vec<f64>> = BTreeMap::new();
+    for samples_row in state
+        .repo
+        .data
+        .get_load_test_samples_by_job_id(job_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get load test samples from the database: {:?}", e);
+            bad_request("Failed to get data from the database")
+        })?
+    {
+        let (Some(sub_job_id), Some(Ok(samples))) = (
+            samples_row.sub_job_id,
+            samples_row.samples.map(serde_json::from_value::<Vec<f64>>),
+        ) else {
+            continue;
+        };
+        load_test_samples
+            .entry(sub_job_id)
+            .or_default()
+            .extend(samples);
+    }
+
+    Ok(load_test_samples)
 }
@@ -47,7 +47,7 @@ pub async fn handle_get_jobs(
     >,
     State(state): State<Arc<AppState>>,
 ) -> Result<ApiResponse<GetJobsResponse>, ApiResponse<()>> {
-    let jobs = state
+    let mut jobs = state
         .repo
         .job
         .get_jobs_with_subjobs(
@@ -63,5 +63,9 @@ pub async fn handle_get_jobs(
             }
         })?;
 
+    for job in jobs.iter_mut() {
+        job.details.redact_headers();
+    }
+
     Ok(ok_response(GetJobsResponse(jobs)))
 }
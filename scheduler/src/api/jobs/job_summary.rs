@@ -0,0 +1,1317 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use common::stats::percentile;
+use rabbitmq::{
+    ConnectionTiming, ErrorKind, HeadResult, LatencyStats, LoadTestResult, LoadedLatency,
+    MeasurementError, PingResult, RandomAccessResult, SoakResult,
+};
+use serde::Serialize;
+use serde_json::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    aggregation::{AggregateInterval, DownloadAggregates, WorkerSteadyState},
+    job_repository::{JobWithSubJobsWithData, SubJobWithData, WorkerData},
+    sub_job_repository::{SubJobStatus, SubJobType},
+};
+
+#[derive(Serialize, ToSchema)]
+pub struct DownloadSpeed {
+    sub_job_id: Uuid,
+    /// Sum of the download speeds of the workers, over-reports when they finish at different times
+    download_speed: f64,
+    /// Aggregate download speed of the workers over the intervals all of them were downloading
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overlap_download_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overlap_window_secs: Option<f64>,
+    /// Bytes downloaded by all workers in each log interval
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    aggregate_intervals: Vec<AggregateInterval>,
+    /// Aggregate download speed of the workers over the intervals all of them were past the TCP
+    /// slow start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    steady_state_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    average_ramp_up_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    steady_state_workers: Vec<WorkerSteadyState>,
+    /// Sum of the download speeds of the workers reading URLs no earlier subjob read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cold_download_speed: Option<f64>,
+    /// Sum of the download speeds of the workers reading URLs an earlier subjob read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cached_download_speed: Option<f64>,
+    /// Download speed of each URL of a multi-URL job
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    source_speeds: Vec<SourceDownloadSpeed>,
+    average_time_to_first_byte_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection_timing: Option<ConnectionTimingSummary>,
+    /// Whether all workers that downloaded the whole of the same range received the same content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_hashes_match: Option<bool>,
+    /// Whether the piece downloaded by every worker matched the piece CID of the URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    piece_cid_verified: Option<bool>,
+    /// Number of workers that downloaded over each HTTP version
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    http_versions: BTreeMap<String, usize>,
+}
+
+/// Download speed of the workers reading one URL of a multi-URL job
+#[derive(Serialize, ToSchema)]
+pub struct SourceDownloadSpeed {
+    url: String,
+    start_range: i64,
+    end_range: i64,
+    workers: usize,
+    download_speed: f64,
+    /// Whether no earlier subjob read the range, so the provider could not have cached it
+    cold: bool,
+}
+
+/// Average duration of each connection phase over the workers that measured it
+#[derive(Serialize, ToSchema)]
+pub struct ConnectionTimingSummary {
+    samples: usize,
+    average_dns_ms: f64,
+    average_connect_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    average_tls_ms: Option<f64>,
+    average_request_sent_ms: f64,
+    average_ttfb_ms: f64,
+    average_total_ms: f64,
+}
+
+/// Latency statistics of the workers, in milliseconds
+///
+/// The percentiles are taken from the samples of all workers together, the other statistics are
+/// averaged over the workers.
+#[derive(Serialize, ToSchema)]
+pub struct LatencySummary {
+    samples: usize,
+    average_ms: f64,
+    average_stddev_ms: f64,
+    average_jitter_ms: f64,
+    /// Missing when the workers stored no samples
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p50_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p95_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p99_ms: Option<f64>,
+    average_loss_percent: f64,
+    /// Number of responses with each HTTP status code
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    status_codes: BTreeMap<u16, usize>,
+}
+
+/// Ping latency before the download compared with the latency during it, in milliseconds
+#[derive(Serialize, ToSchema)]
+pub struct LoadedLatencySummary {
+    samples: usize,
+    average_idle_ms: f64,
+    average_loaded_ms: f64,
+    /// 95th percentile of the loaded samples of all workers together
+    loaded_p95_ms: f64,
+    /// Average increase of the latency under load
+    average_increase_ms: f64,
+    average_loaded_loss_percent: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UploadSpeed {
+    sub_job_id: Uuid,
+    upload_speed: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RetrievalSpeed {
+    sub_job_id: Uuid,
+    retrieval_speed: f64,
+    blocks: usize,
+    verified_blocks: usize,
+}
+
+/// Raw TCP throughput of the iperf3 sub job, summed over the workers
+#[derive(Serialize, ToSchema)]
+pub struct Iperf3Speed {
+    sub_job_id: Uuid,
+    iperf3_speed: f64,
+    /// Server TCP retransmits, only when the server platform reports them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    server_retransmits: Option<i64>,
+}
+
+/// Drop of the aggregate soak throughput reported as throttling
+const THROTTLE_DROP_PERCENT: f64 = 30.0;
+/// Windows needed on each side of a throughput step, a single slow window is not throttling
+const MIN_STEP_WINDOWS: usize = 2;
+
+/// Success rate below which the provider is considered erroring at the target rate
+const LOAD_TEST_SUCCESS_THRESHOLD: f64 = 0.99;
+
+/// Upper bounds of the seek latency histogram buckets, in milliseconds
+const SEEK_LATENCY_BUCKETS_MS: [f64; 10] =
+    [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0];
+
+/// Time to first byte of the random range requests of all workers, cached content is served in
+/// about the round trip time while disks add their seek time
+#[derive(Serialize, ToSchema)]
+pub struct SeekLatencyProfile {
+    sub_job_id: Uuid,
+    requests: usize,
+    failed_requests: usize,
+    /// Requests per second of all workers together
+    requests_per_sec: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    /// Ping latency of the workers, the network part of the time to first byte
+    #[serde(skip_serializing_if = "Option::is_none")]
+    average_ping_ms: Option<f64>,
+    /// Median time to first byte without the ping latency, an estimate of the server time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    server_p50_ms: Option<f64>,
+    histogram: Vec<SeekLatencyBucket>,
+}
+
+/// Number of requests with a time to first byte up to the bound, the last bucket has no bound
+#[derive(Serialize, ToSchema)]
+pub struct SeekLatencyBucket {
+    upper_ms: Option<f64>,
+    requests: usize,
+}
+
+/// Success rate and latency of the load test at one target rate, all workers together
+#[derive(Serialize, ToSchema)]
+pub struct RateLatencyPoint {
+    sub_job_id: Uuid,
+    target_rps: i64,
+    /// Successful requests per second of all workers together
+    achieved_rps: f64,
+    requests: usize,
+    succeeded: usize,
+    success_rate: f64,
+    #[schema(example = json!({"206": 980, "503": 20}))]
+    status_codes: BTreeMap<u16, usize>,
+    /// Failed requests by error kind
+    errors: BTreeMap<String, usize>,
+    /// Latency percentiles of the successful requests, not set when none succeeded
+    p50_ms: Option<f64>,
+    p95_ms: Option<f64>,
+    p99_ms: Option<f64>,
+}
+
+/// Aggregate download speed of a saturation search stage
+#[derive(Serialize, ToSchema)]
+pub struct SaturationStage {
+    sub_job_id: Uuid,
+    workers: i64,
+    aggregate_speed: f64,
+    /// Gain of the aggregate speed over the previous stage, not set for the first stage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gain_percent: Option<f64>,
+}
+
+/// Completed stages of the saturation search and the point where more workers stop paying off
+#[derive(Serialize, ToSchema)]
+pub struct SaturationSearchReport {
+    stages: Vec<SaturationStage>,
+    /// Workers of the last stage before the gain fell below the threshold, not set when the
+    /// aggregate speed still grew with every worker
+    #[serde(skip_serializing_if = "Option::is_none")]
+    knee_workers: Option<i64>,
+    /// Highest aggregate download speed of the stages
+    max_sustainable_speed: f64,
+}
+
+/// Aggregate throughput of all workers in one sampling window of the soak
+#[derive(Serialize, ToSchema)]
+pub struct SoakPoint {
+    offset_secs: u64,
+    aggregate_speed: f64,
+    /// Bytes received by all workers since the start of the soak, at the end of the window
+    cumulative_bytes: usize,
+}
+
+/// Step where the aggregate throughput of the soak dropped and stayed down
+#[derive(Serialize, ToSchema)]
+pub struct ThrottlingStep {
+    /// Start of the first window after the drop
+    after_secs: u64,
+    /// Bytes received by all workers before the drop
+    after_bytes: usize,
+    after_bytes_per_worker: usize,
+    drop_percent: f64,
+}
+
+/// Throughput of the soak over time, the burst and sustained bandwidth are the same when the
+/// throughput did not drop
+#[derive(Serialize, ToSchema)]
+pub struct SoakReport {
+    sub_job_id: Uuid,
+    workers: usize,
+    sample_interval_secs: u64,
+    time_series: Vec<SoakPoint>,
+    /// Average aggregate speed before the throttling step
+    burst_speed: f64,
+    /// Average aggregate speed after the throttling step
+    sustained_speed: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttling: Option<ThrottlingStep>,
+}
+
+/// Number of failed measurements of the sub job by measurement and error kind
+#[derive(Serialize, ToSchema)]
+pub struct SubJobErrorHistogram {
+    sub_job_id: Uuid,
+    failed_workers: usize,
+    #[schema(example = json!({"download": {"connect_timeout": 2, "http_status_503": 1}}))]
+    errors: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct JobSummary {
+    pub max_download_speed: Option<f64>,
+    /// Highest aggregate download speed over the intervals all workers were downloading, corrects
+    /// `max_download_speed` when the workers finish at different times
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_overlap_download_speed: Option<f64>,
+    /// Highest aggregate download speed of the workers after the TCP slow start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_steady_state_speed: Option<f64>,
+    /// Highest download speed of the workers reading URLs for the first time in multi-URL jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cold_download_speed: Option<f64>,
+    /// Highest download speed of the workers reading URLs read before in multi-URL jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cached_download_speed: Option<f64>,
+    pub download_speeds: Option<Vec<DownloadSpeed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_timing: Option<ConnectionTimingSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_upload_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_speeds: Option<Vec<UploadSpeed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retrieval_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retrieval_speeds: Option<Vec<RetrievalSpeed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_iperf3_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iperf3_speeds: Option<Vec<Iperf3Speed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seek_latency_profiles: Option<Vec<SeekLatencyProfile>>,
+    /// Load test results by target rate, from the lowest to the highest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_test_curve: Option<Vec<RateLatencyPoint>>,
+    /// Lowest target rate with less than 99% successful requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_erroring_rps: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation_search: Option<SaturationSearchReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soak: Option<SoakReport>,
+    pub average_end_latency: Option<f64>,
+    pub average_gateway_latency: Option<f64>,
+    /// HEAD request latency to the URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_latency: Option<LatencySummary>,
+    /// Ping latency to the host of the URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway_latency: Option<LatencySummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loaded_latency: Option<LoadedLatencySummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub error_histograms: Vec<SubJobErrorHistogram>,
+}
+
+/// Data of the summary kept out of the job data, the samples stripped from the job data when not
+/// extended and the aggregates of the download interval logs
+pub(crate) struct SummaryData {
+    pub(crate) download_aggregates: BTreeMap<Uuid, DownloadAggregates>,
+    /// Samples of the ping, head and random access measurements by worker data
+    pub(crate) latency_samples: BTreeMap<Uuid, WorkerLatencySamples>,
+    /// Latency samples of the load test steps by sub job
+    pub(crate) load_test_samples: BTreeMap<Uuid, Vec<f64>>,
+}
+
+/// Summarize the measurements of all sub jobs of the job
+pub(crate) fn summarize_job(job: &JobWithSubJobsWithData, mut data: SummaryData) -> JobSummary {
+    let mut source_speeds = source_download_speeds(job);
+
+    let download_speeds_iter = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::CombinedDHP)
+        .map(|sub_job| {
+            let sub_job_download_speed = sub_job.worker_data.iter().map(|wd| {
+                wd.download
+                    .get("download_speed")
+                    .unwrap_or(&json!(0.0))
+                    .as_f64()
+                    .unwrap_or(0.0)
+            });
+
+            let sub_job_ttfb = sub_job.worker_data.iter().map(|wd| {
+                wd.download
+                    .get("time_to_first_byte_ms")
+                    .unwrap_or(&json!(0.0))
+                    .as_f64()
+                    .unwrap_or(0.0)
+            });
+
+            let sub_job_ttfb_sum = sub_job_ttfb.sum::<f64>();
+            let worker_count = sub_job.worker_data.len() as f64;
+            let average_ttfb = if worker_count > 0.0 {
+                sub_job_ttfb_sum / worker_count
+            } else {
+                0.0
+            };
+
+            let aggregates = data
+                .download_aggregates
+                .remove(&sub_job.id)
+                .unwrap_or_default();
+            let source_speeds = source_speeds.remove(&sub_job.id).unwrap_or_default();
+            let grouped_speed = |cold: bool| {
+                let speeds: Vec<f64> = source_speeds
+                    .iter()
+                    .filter(|source| source.cold == cold)
+                    .map(|source| source.download_speed)
+                    .collect();
+                Some(speeds.iter().sum()).filter(|_| !speeds.is_empty())
+            };
+
+            DownloadSpeed {
+                sub_job_id: sub_job.id,
+                download_speed: sub_job_download_speed.sum::<f64>(),
+                overlap_download_speed: aggregates.overlap_download_speed,
+                overlap_window_secs: aggregates.overlap_window_secs,
+                aggregate_intervals: aggregates.aggregate_intervals,
+                steady_state_speed: aggregates.steady_state_speed,
+                average_ramp_up_secs: aggregates.average_ramp_up_secs,
+                steady_state_workers: aggregates.steady_state_workers,
+                cold_download_speed: grouped_speed(true),
+                cached_download_speed: grouped_speed(false),
+                source_speeds,
+                average_time_to_first_byte_ms: average_ttfb,
+                connection_timing: summarize_connection_timings(&get_connection_timings(
+                    &sub_job.worker_data,
+                )),
+                content_hashes_match: compare_content_hashes(
+                    &sub_job.worker_data,
+                    sub_job_assignments(sub_job).as_ref(),
+                ),
+                piece_cid_verified: sub_job
+                    .worker_data
+                    .iter()
+                    .map(|wd| wd.download.get("piece_cid_verified")?.as_bool())
+                    .reduce(|a, b| Some(a? && b?))
+                    .flatten(),
+                http_versions: sub_job
+                    .worker_data
+                    .iter()
+                    .filter_map(|wd| wd.download.get("protocol")?.get("http_version")?.as_str())
+                    .fold(BTreeMap::new(), |mut versions, version| {
+                        *versions.entry(version.to_string()).or_default() += 1;
+                        versions
+                    }),
+            }
+        });
+
+    let download_speeds: Vec<DownloadSpeed> = download_speeds_iter.collect();
+
+    let max_download_speed = download_speeds
+        .iter()
+        .map(|ds| ds.download_speed)
+        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap_or(0.0);
+
+    let max_overlap_download_speed = download_speeds
+        .iter()
+        .filter_map(|ds| ds.overlap_download_speed)
+        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let max_steady_state_speed = download_speeds
+        .iter()
+        .filter_map(|ds| ds.steady_state_speed)
+        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let max_cold_download_speed = download_speeds
+        .iter()
+        .filter_map(|ds| ds.cold_download_speed)
+        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let max_cached_download_speed = download_speeds
+        .iter()
+        .filter_map(|ds| ds.cached_download_speed)
+        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let connection_timings: Vec<ConnectionTiming> = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::CombinedDHP)
+        .flat_map(|sub_job| get_connection_timings(&sub_job.worker_data))
+        .collect();
+
+    let samples_of = |wd: &WorkerData, field: fn(&WorkerLatencySamples) -> &Vec<f64>| {
+        data.latency_samples
+            .get(&wd.id)
+            .map(|samples| field(samples).clone())
+            .unwrap_or_default()
+    };
+
+    let dhp_worker_data = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::CombinedDHP)
+        .flat_map(|sub_job| sub_job.worker_data.iter());
+
+    let head_results: Vec<HeadResult> = dhp_worker_data
+        .clone()
+        .filter_map(|wd| {
+            let mut head: HeadResult = serde_json::from_value(wd.head.clone()).ok()?;
+            head.stats.samples = samples_of(wd, |samples| &samples.head);
+            Some(head)
+        })
+        .collect();
+    let end_latency = summarize_latencies(
+        head_results.iter().map(|r| (r.avg, &r.stats)).collect(),
+        head_results.iter().flat_map(|r| r.status_codes.iter()),
+        1.0,
+    );
+
+    let loaded_latency = summarize_loaded_latencies(
+        &dhp_worker_data
+            .clone()
+            .filter_map(|wd| {
+                let ping: PingResult = serde_json::from_value(wd.ping.clone()).ok()?;
+                let loaded_latency: LoadedLatency =
+                    serde_json::from_value(wd.download.get("loaded_latency")?.clone()).ok()?;
+                Some((ping, loaded_latency))
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    // Ping latencies are measured in seconds
+    let ping_results: Vec<PingResult> = dhp_worker_data
+        .filter_map(|wd| {
+            let mut ping: PingResult = serde_json::from_value(wd.ping.clone()).ok()?;
+            ping.stats.samples = samples_of(wd, |samples| &samples.ping);
+            Some(ping)
+        })
+        .collect();
+    let gateway_latency = summarize_latencies(
+        ping_results.iter().map(|r| (r.avg, &r.stats)).collect(),
+        std::iter::empty(),
+        1000.0,
+    );
+
+    let upload_speeds: Vec<UploadSpeed> = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::Upload)
+        .map(|sub_job| UploadSpeed {
+            sub_job_id: sub_job.id,
+            upload_speed: sub_job
+                .worker_data
+                .iter()
+                .filter_map(|wd| wd.upload.as_ref()?.get("upload_speed")?.as_f64())
+                .sum::<f64>(),
+        })
+        .collect();
+
+    let (max_upload_speed, upload_speeds) = if upload_speeds.is_empty() {
+        (None, None)
+    } else {
+        let max_upload_speed = upload_speeds
+            .iter()
+            .map(|us| us.upload_speed)
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        (max_upload_speed, Some(upload_speeds))
+    };
+
+    let retrieval_speeds: Vec<RetrievalSpeed> = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::Retrieval)
+        .map(|sub_job| {
+            let retrievals: Vec<&serde_json::Value> = sub_job
+                .worker_data
+                .iter()
+                .filter_map(|wd| wd.retrieval.as_ref())
+                .collect();
+            let sum_of = |field: &str| {
+                retrievals
+                    .iter()
+                    .filter_map(|r| r.get(field)?.as_f64())
+                    .sum::<f64>()
+            };
+
+            RetrievalSpeed {
+                sub_job_id: sub_job.id,
+                retrieval_speed: sum_of("retrieval_speed"),
+                blocks: sum_of("blocks") as usize,
+                verified_blocks: sum_of("verified_blocks") as usize,
+            }
+        })
+        .collect();
+
+    let (max_retrieval_speed, retrieval_speeds) = if retrieval_speeds.is_empty() {
+        (None, None)
+    } else {
+        let max_retrieval_speed = retrieval_speeds
+            .iter()
+            .map(|rs| rs.retrieval_speed)
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        (max_retrieval_speed, Some(retrieval_speeds))
+    };
+
+    let iperf3_speeds: Vec<Iperf3Speed> = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::Iperf3)
+        .map(|sub_job| {
+            let iperf3_results: Vec<&serde_json::Value> = sub_job
+                .worker_data
+                .iter()
+                .filter_map(|wd| wd.iperf3.as_ref())
+                .collect();
+
+            Iperf3Speed {
+                sub_job_id: sub_job.id,
+                iperf3_speed: iperf3_results
+                    .iter()
+                    .filter_map(|r| r.get("iperf3_speed")?.as_f64())
+                    .sum::<f64>(),
+                server_retransmits: iperf3_results
+                    .iter()
+                    .filter_map(|r| r.get("server_retransmits")?.as_i64())
+                    .reduce(|a, b| a + b),
+            }
+        })
+        .collect();
+
+    let (max_iperf3_speed, iperf3_speeds) = if iperf3_speeds.is_empty() {
+        (None, None)
+    } else {
+        let max_iperf3_speed = iperf3_speeds
+            .iter()
+            .map(|is| is.iperf3_speed)
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        (max_iperf3_speed, Some(iperf3_speeds))
+    };
+
+    let seek_latency_profiles: Vec<SeekLatencyProfile> = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::RandomAccess)
+        .filter_map(|sub_job| profile_seek_latency(sub_job, &data.latency_samples))
+        .collect();
+
+    let mut load_test_curve: Vec<RateLatencyPoint> = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::LoadTest)
+        .filter_map(|sub_job| {
+            summarize_load_test(
+                sub_job,
+                data.load_test_samples
+                    .remove(&sub_job.id)
+                    .unwrap_or_default(),
+            )
+        })
+        .collect();
+    load_test_curve.sort_by_key(|point| point.target_rps);
+    let first_erroring_rps = load_test_curve
+        .iter()
+        .find(|point| point.success_rate < LOAD_TEST_SUCCESS_THRESHOLD)
+        .map(|point| point.target_rps);
+
+    let saturation_search = report_saturation_search(
+        &job.sub_jobs,
+        job.details.saturation_gain_percent.unwrap_or(10) as f64,
+    );
+
+    let soak = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::Soak)
+        .find_map(report_soak);
+
+    let error_histograms: Vec<SubJobErrorHistogram> = job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| !sub_job.worker_data.is_empty())
+        .map(|sub_job| SubJobErrorHistogram {
+            sub_job_id: sub_job.id,
+            failed_workers: sub_job
+                .worker_data
+                .iter()
+                .filter(|wd| wd.is_success == Some(false))
+                .count(),
+            errors: count_errors(&sub_job.worker_data),
+        })
+        .collect();
+
+    JobSummary {
+        max_download_speed: Some(max_download_speed),
+        max_overlap_download_speed,
+        max_steady_state_speed,
+        max_cold_download_speed,
+        max_cached_download_speed,
+        download_speeds: Some(download_speeds),
+        connection_timing: summarize_connection_timings(&connection_timings),
+        max_upload_speed,
+        upload_speeds,
+        max_retrieval_speed,
+        retrieval_speeds,
+        max_iperf3_speed,
+        iperf3_speeds,
+        seek_latency_profiles: Some(seek_latency_profiles).filter(|p| !p.is_empty()),
+        load_test_curve: Some(load_test_curve).filter(|c| !c.is_empty()),
+        first_erroring_rps,
+        saturation_search,
+        soak,
+        average_end_latency: end_latency.as_ref().map(|l| l.average_ms),
+        average_gateway_latency: gateway_latency.as_ref().map(|l| l.average_ms),
+        end_latency,
+        gateway_latency,
+        loaded_latency,
+        error_histograms,
+    }
+}
+
+/// Download speed of each URL of a multi-URL job by sub job, a URL is read cold by the first sub
+/// job its workers were assigned to and cached by the later ones
+fn source_download_speeds(
+    job: &JobWithSubJobsWithData,
+) -> BTreeMap<Uuid, Vec<SourceDownloadSpeed>> {
+    let mut speeds = BTreeMap::new();
+    let Some(sources) = &job.details.sources else {
+        return speeds;
+    };
+
+    let mut read_sources = BTreeSet::new();
+    for sub_job in job
+        .sub_jobs
+        .iter()
+        .filter(|sub_job| sub_job.r#type == SubJobType::CombinedDHP)
+    {
+        let Some(assignments) = sub_job_assignments(sub_job) else {
+            continue;
+        };
+
+        let mut source_totals: BTreeMap<usize, (usize, f64)> = BTreeMap::new();
+        for wd in &sub_job.worker_data {
+            let Some(source) = assignments.get(&wd.worker_name) else {
+                continue;
+            };
+            let (workers, download_speed) = source_totals.entry(*source).or_default();
+            *workers += 1;
+            *download_speed += wd
+                .download
+                .get("download_speed")
+                .and_then(|speed| speed.as_f64())
+                .unwrap_or(0.0);
+        }
+
+        let sub_job_speeds = source_totals
+            .into_iter()
+            .filter_map(|(source, (workers, download_speed))| {
+                let range = sources.get(source)?;
+                Some(SourceDownloadSpeed {
+                    url: range.url.clone(),
+                    start_range: range.start_range,
+                    end_range: range.end_range,
+                    workers,
+                    download_speed,
+                    cold: !read_sources.contains(&source),
+                })
+            })
+            .collect();
+        // The provider saw the requests of the failed workers as well
+        read_sources.extend(assignments.into_values());
+        speeds.insert(sub_job.id, sub_job_speeds);
+    }
+
+    speeds
+}
+
+/// Count the errors of every measurement by their kind
+///
+/// Results stored before the errors were typed only have the message, they are counted as
+/// `unknown`.
+fn count_errors(worker_data: &[WorkerData]) -> BTreeMap<String, BTreeMap<String, usize>> {
+    let mut errors: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+
+    for wd in worker_data {
+        let measurements = [
+            ("download", Some(&wd.download)),
+            ("ping", Some(&wd.ping)),
+            ("head", Some(&wd.head)),
+            ("upload", wd.upload.as_ref()),
+            ("retrieval", wd.retrieval.as_ref()),
+            ("iperf3", wd.iperf3.as_ref()),
+            ("random_access", wd.random_access.as_ref()),
+            ("load_test", wd.load_test.as_ref()),
+            ("soak", wd.soak.as_ref()),
+        ];
+
+        for (measurement, value) in measurements {
+            let Some(value) = value.filter(|value| value.get("error").is_some()) else {
+                continue;
+            };
+            let kind = match serde_json::from_value::<MeasurementError>(value.clone()) {
+                Ok(error) if error.kind == ErrorKind::NotApplicable => continue,
+                Ok(error) => error.kind.name(),
+                Err(_) => "unknown".to_string(),
+            };
+
+            *errors
+                .entry(measurement.to_string())
+                .or_default()
+                .entry(kind)
+                .or_default() += 1;
+        }
+    }
+
+    errors
+}
+
+/// Source index of each worker of a multi-URL sub job by worker name
+fn sub_job_assignments(sub_job: &SubJobWithData) -> Option<BTreeMap<String, usize>> {
+    serde_json::from_value(sub_job.details.get("assignments")?.clone()).ok()
+}
+
+/// Compare the content hashes of the workers, every worker of a sub job downloads the same range
+/// unless the sub job assigned the sources of a multi-URL job, then the workers of each source do
+///
+/// With parallel streams the hashes are compared stream by stream, as the range is split the same
+/// way on every worker.
+fn compare_content_hashes(
+    worker_data: &[WorkerData],
+    assignments: Option<&BTreeMap<String, usize>>,
+) -> Option<bool> {
+    let range_check_hashes = |download: &serde_json::Value| {
+        download
+            .get("range_check")?
+            .get("sha256")?
+            .as_str()
+            .map(str::to_string)
+    };
+
+    let hashes: Vec<((Option<usize>, usize), String)> = worker_data
+        .iter()
+        .flat_map(|wd| {
+            let source =
+                assignments.and_then(|assignments| assignments.get(&wd.worker_name).copied());
+            let streams = wd.download.get("streams").and_then(|s| s.as_array());
+            match streams {
+                Some(streams) if !streams.is_empty() => streams
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, stream)| {
+                        Some(((source, index), range_check_hashes(stream)?))
+                    })
+                    .collect::<Vec<_>>(),
+                _ => range_check_hashes(&wd.download)
+                    .map(|hash| vec![((source, 0), hash)])
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    if hashes.is_empty() {
+        return None;
+    }
+
+    let mut first_hashes: BTreeMap<(Option<usize>, usize), &String> = BTreeMap::new();
+    Some(
+        hashes
+            .iter()
+            .all(|(index, hash)| *first_hashes.entry(*index).or_insert(hash) == hash),
+    )
+}
+
+/// Get the connection timings of the workers that managed to measure them
+fn get_connection_timings(worker_data: &[WorkerData]) -> Vec<ConnectionTiming> {
+    worker_data
+        .iter()
+        .filter_map(|wd| serde_json::from_value(wd.download.get("connection_timing")?.clone()).ok())
+        .collect()
+}
+
+fn summarize_connection_timings(timings: &[ConnectionTiming]) -> Option<ConnectionTimingSummary> {
+    if timings.is_empty() {
+        return None;
+    }
+
+    let average = |values: Vec<f64>| {
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.iter().sum::<f64>() / values.len() as f64)
+        }
+    };
+    let average_of = |phase: fn(&ConnectionTiming) -> f64| {
+        average(timings.iter().map(phase).collect()).unwrap_or(0.0)
+    };
+
+    Some(ConnectionTimingSummary {
+        samples: timings.len(),
+        average_dns_ms: average_of(|t| t.dns_ms),
+        average_connect_ms: average_of(|t| t.connect_ms),
+        average_tls_ms: average(timings.iter().filter_map(|t| t.tls_ms).collect()),
+        average_request_sent_ms: average_of(|t| t.request_sent_ms),
+        average_ttfb_ms: average_of(|t| t.ttfb_ms),
+        average_total_ms: average_of(|t| t.total_ms),
+    })
+}
+
+/// Summarize the latency results of the workers, `scale` converts the results to milliseconds
+fn summarize_latencies<'a>(
+    results: Vec<(f64, &LatencyStats)>,
+    status_codes: impl Iterator<Item = &'a u16>,
+    scale: f64,
+) -> Option<LatencySummary> {
+    if results.is_empty() {
+        return None;
+    }
+
+    let average = |values: Vec<f64>| {
+        if values.is_empty() {
+            0.0
+        } else {
+            values.iter().sum::<f64>() / values.len() as f64
+        }
+    };
+
+    // Results stored before the distribution was measured have no samples
+    let stats: Vec<&LatencyStats> = results
+        .iter()
+        .map(|(_, stats)| *stats)
+        .filter(|stats| !stats.samples.is_empty())
+        .collect();
+    let average_of =
+        |stat: fn(&LatencyStats) -> f64| average(stats.iter().map(|s| stat(s)).collect());
+
+    // Averaging the percentiles of the workers would hide the tail of the slow ones
+    let mut samples: Vec<f64> = stats
+        .iter()
+        .flat_map(|s| s.samples.iter().copied())
+        .collect();
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let pooled_percentile = |p: f64| (!samples.is_empty()).then(|| percentile(&samples, p) * scale);
+
+    let mut status_code_counts = BTreeMap::new();
+    for status_code in status_codes {
+        *status_code_counts.entry(*status_code).or_insert(0) += 1;
+    }
+
+    Some(LatencySummary {
+        samples: results.len(),
+        average_ms: average(results.iter().map(|(avg, _)| *avg).collect()) * scale,
+        average_stddev_ms: average_of(|s| s.stddev) * scale,
+        average_jitter_ms: average_of(|s| s.jitter) * scale,
+        p50_ms: pooled_percentile(50.0),
+        p95_ms: pooled_percentile(95.0),
+        p99_ms: pooled_percentile(99.0),
+        average_loss_percent: average_of(|s| s.loss_percent),
+        status_codes: status_code_counts,
+    })
+}
+
+/// Compare the idle ping latency of each worker with the latency it measured during the download
+fn summarize_loaded_latencies(
+    results: &[(PingResult, LoadedLatency)],
+) -> Option<LoadedLatencySummary> {
+    let workers: Vec<(f64, Vec<f64>, f64)> = results
+        .iter()
+        .filter_map(|(ping, loaded_latency)| {
+            let loaded: Vec<f64> = loaded_latency
+                .samples
+                .iter()
+                .filter_map(|(_, rtt_ms)| *rtt_ms)
+                .collect();
+            if loaded.is_empty() {
+                return None;
+            }
+
+            let lost = loaded_latency.samples.len() - loaded.len();
+            let loss_percent = lost as f64 / loaded_latency.samples.len() as f64 * 100.0;
+
+            // Ping latencies are measured in seconds
+            Some((ping.avg * 1000.0, loaded, loss_percent))
+        })
+        .collect();
+
+    if workers.is_empty() {
+        return None;
+    }
+
+    let average = |values: Vec<f64>| values.iter().sum::<f64>() / values.len() as f64;
+    let average_idle_ms = average(workers.iter().map(|(idle, _, _)| *idle).collect());
+    let average_loaded_ms = average(
+        workers
+            .iter()
+            .map(|(_, loaded, _)| average(loaded.clone()))
+            .collect(),
+    );
+
+    let mut pooled_loaded: Vec<f64> = workers
+        .iter()
+        .flat_map(|(_, loaded, _)| loaded.iter().copied())
+        .collect();
+    pooled_loaded.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    Some(LoadedLatencySummary {
+        samples: workers.len(),
+        average_idle_ms,
+        average_loaded_ms,
+        loaded_p95_ms: percentile(&pooled_loaded, 95.0),
+        average_increase_ms: average_loaded_ms - average_idle_ms,
+        average_loaded_loss_percent: average(
+            workers
+                .iter()
+                .map(|(_, _, loss_percent)| *loss_percent)
+                .collect(),
+        ),
+    })
+}
+
+/// Latency samples of a worker
+#[derive(Default)]
+pub(crate) struct WorkerLatencySamples {
+    pub(crate) ping: Vec<f64>,
+    pub(crate) head: Vec<f64>,
+    pub(crate) ttfb: Vec<f64>,
+}
+
+/// Profile the time to first byte of the random range requests of all workers of the sub job
+fn profile_seek_latency(
+    sub_job: &SubJobWithData,
+    latency_samples: &BTreeMap<Uuid, WorkerLatencySamples>,
+) -> Option<SeekLatencyProfile> {
+    let results: Vec<RandomAccessResult> = sub_job
+        .worker_data
+        .iter()
+        .filter_map(|wd| serde_json::from_value(wd.random_access.clone()?).ok())
+        .collect();
+
+    let mut samples: Vec<f64> = sub_job
+        .worker_data
+        .iter()
+        .filter_map(|wd| latency_samples.get(&wd.id))
+        .flat_map(|samples| samples.ttfb.iter().copied())
+        .collect();
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    // Ping latencies are measured in seconds
+    let pings: Vec<f64> = sub_job
+        .worker_data
+        .iter()
+        .filter_map(|wd| serde_json::from_value::<PingResult>(wd.ping.clone()).ok())
+        .map(|ping| ping.avg * 1000.0)
+        .collect();
+    let average_ping_ms =
+        Some(pings.iter().sum::<f64>() / pings.len() as f64).filter(|_| !pings.is_empty());
+
+    let mut histogram: Vec<SeekLatencyBucket> = SEEK_LATENCY_BUCKETS_MS
+        .iter()
+        .map(|upper_ms| Some(*upper_ms))
+        .chain(std::iter::once(None))
+        .map(|upper_ms| SeekLatencyBucket {
+            upper_ms,
+            requests: 0,
+        })
+        .collect();
+    for sample in &samples {
+        let bucket = SEEK_LATENCY_BUCKETS_MS
+            .iter()
+            .position(|upper_ms| sample <= upper_ms)
+            .unwrap_or(SEEK_LATENCY_BUCKETS_MS.len());
+        histogram[bucket].requests += 1;
+    }
+
+    let p50_ms = percentile(&samples, 50.0);
+
+    Some(SeekLatencyProfile {
+        sub_job_id: sub_job.id,
+        requests: samples.len(),
+        failed_requests: results.iter().map(|r| r.failed_requests).sum(),
+        requests_per_sec: results.iter().map(|r| r.requests_per_sec).sum(),
+        p50_ms,
+        p90_ms: percentile(&samples, 90.0),
+        p99_ms: percentile(&samples, 99.0),
+        average_ping_ms,
+        server_p50_ms: average_ping_ms.map(|ping_ms| (p50_ms - ping_ms).max(0.0)),
+        histogram,
+    })
+}
+
+/// Compare the aggregate download speed of the completed saturation search stages, the sub jobs
+/// are ordered by creation so the stages come with growing worker counts
+///
+/// The speed of a stage is the one the search compared when it completed the stage.
+fn report_saturation_search(
+    sub_jobs: &[SubJobWithData],
+    min_gain_percent: f64,
+) -> Option<SaturationSearchReport> {
+    let mut stages: Vec<SaturationStage> = Vec::new();
+    for sub_job in sub_jobs.iter().filter(|sub_job| {
+        sub_job.r#type == SubJobType::SaturationSearch
+            && matches!(sub_job.status, SubJobStatus::Completed)
+    }) {
+        let aggregate_speed = sub_job
+            .details
+            .get("aggregate_speed")
+            .and_then(|aggregate_speed| aggregate_speed.as_f64())
+            .unwrap_or_default();
+        let gain_percent = stages
+            .last()
+            .map(|previous| previous.aggregate_speed)
+            .filter(|previous_speed| *previous_speed > 0.0)
+            .map(|previous_speed| (aggregate_speed - previous_speed) / previous_speed * 100.0);
+
+        stages.push(SaturationStage {
+            sub_job_id: sub_job.id,
+            workers: sub_job
+                .details
+                .get("workers_count")
+                .and_then(|workers_count| workers_count.as_i64())
+                .unwrap_or(sub_job.worker_data.len() as i64),
+            aggregate_speed,
+            gain_percent,
+        });
+    }
+    if stages.is_empty() {
+        return None;
+    }
+
+    let knee_workers = stages
+        .windows(2)
+        .find(|pair| {
+            pair[1]
+                .gain_percent
+                .is_some_and(|gain_percent| gain_percent < min_gain_percent)
+        })
+        .map(|pair| pair[0].workers);
+    let max_sustainable_speed = stages
+        .iter()
+        .map(|stage| stage.aggregate_speed)
+        .fold(0.0, f64::max);
+
+    Some(SaturationSearchReport {
+        stages,
+        knee_workers,
+        max_sustainable_speed,
+    })
+}
+
+/// Add up the sampling windows of all workers of the soak and look for a throttling step
+fn report_soak(sub_job: &SubJobWithData) -> Option<SoakReport> {
+    let results: Vec<SoakResult> = sub_job
+        .worker_data
+        .iter()
+        .filter_map(|wd| serde_json::from_value(wd.soak.clone()?).ok())
+        .collect();
+    let sample_interval_secs = results.first()?.sample_interval_secs;
+
+    // The windows start together on every worker, the same offset is the same time
+    let mut windows: BTreeMap<u64, (f64, usize)> = BTreeMap::new();
+    for sample in results.iter().flat_map(|r| r.samples.iter()) {
+        let (speed, bytes) = windows.entry(sample.offset_secs).or_default();
+        *speed += sample.speed;
+        *bytes += sample.bytes;
+    }
+
+    let mut cumulative_bytes = 0;
+    let time_series: Vec<SoakPoint> = windows
+        .into_iter()
+        .map(|(offset_secs, (aggregate_speed, bytes))| {
+            cumulative_bytes += bytes;
+            SoakPoint {
+                offset_secs,
+                aggregate_speed,
+                cumulative_bytes,
+            }
+        })
+        .collect();
+
+    let speeds: Vec<f64> = time_series.iter().map(|p| p.aggregate_speed).collect();
+    let mean = |speeds: &[f64]| speeds.iter().sum::<f64>() / speeds.len().max(1) as f64;
+    let step = detect_throughput_step(&speeds);
+    let (burst_speed, sustained_speed) = match step {
+        Some(step) => (mean(&speeds[..step]), mean(&speeds[step..])),
+        None => (mean(&speeds), mean(&speeds)),
+    };
+    let throttling = step.map(|step| {
+        let after_bytes = time_series[step - 1].cumulative_bytes;
+        ThrottlingStep {
+            after_secs: time_series[step].offset_secs,
+            after_bytes,
+            after_bytes_per_worker: after_bytes / results.len(),
+            drop_percent: (burst_speed - sustained_speed) / burst_speed * 100.0,
+        }
+    });
+
+    Some(SoakReport {
+        sub_job_id: sub_job.id,
+        workers: results.len(),
+        sample_interval_secs,
+        time_series,
+        burst_speed,
+        sustained_speed,
+        throttling,
+    })
+}
+
+/// Index of the first window after a step down of the throughput, the split weighs the drop by
+/// the windows on both sides so a few noisy windows at the start don't make a step
+fn detect_throughput_step(speeds: &[f64]) -> Option<usize> {
+    if speeds.len() < 2 * MIN_STEP_WINDOWS {
+        return None;
+    }
+
+    let total: f64 = speeds.iter().sum();
+    let mut before = 0.0;
+    let mut best: Option<(usize, f64, f64, f64)> = None;
+    for split in 1..=speeds.len() - MIN_STEP_WINDOWS {
+        before += speeds[split - 1];
+        if split < MIN_STEP_WINDOWS {
+            continue;
+        }
+
+        let mean_before = before / split as f64;
+        let mean_after = (total - before) / (speeds.len() - split) as f64;
+        let score = (mean_before - mean_after) * ((split * (speeds.len() - split)) as f64).sqrt();
+        if best.is_none_or(|(_, best_score, _, _)| score > best_score) {
+            best = Some((split, score, mean_before, mean_after));
+        }
+    }
+
+    best.filter(|(_, _, mean_before, mean_after)| {
+        *mean_before > 0.0
+            && (mean_before - mean_after) / mean_before * 100.0 >= THROTTLE_DROP_PERCENT
+    })
+    .map(|(split, _, _, _)| split)
+}
+
+/// Aggregate the load test results of all workers of the sub job at its target rate, the latency
+/// percentiles are taken from the pooled samples of the workers
+fn summarize_load_test(
+    sub_job: &SubJobWithData,
+    mut samples: Vec<f64>,
+) -> Option<RateLatencyPoint> {
+    let target_rps = sub_job.details.get("target_rps")?.as_i64()?;
+    let results: Vec<LoadTestResult> = sub_job
+        .worker_data
+        .iter()
+        .filter_map(|wd| serde_json::from_value(wd.load_test.clone()?).ok())
+        .collect();
+    if results.is_empty() {
+        return None;
+    }
+
+    let mut status_codes: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut errors: BTreeMap<String, usize> = BTreeMap::new();
+    for result in &results {
+        for (status_code, count) in &result.status_codes {
+            *status_codes.entry(*status_code).or_default() += count;
+        }
+        for (kind, count) in &result.errors {
+            *errors.entry(kind.clone()).or_default() += count;
+        }
+    }
+
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let sample_percentile = |p| (!samples.is_empty()).then(|| percentile(&samples, p));
+
+    let requests: usize = results.iter().map(|r| r.requests).sum();
+    let succeeded: usize = results.iter().map(|r| r.succeeded).sum();
+
+    Some(RateLatencyPoint {
+        sub_job_id: sub_job.id,
+        target_rps,
+        achieved_rps: results.iter().map(|r| r.achieved_rate).sum(),
+        requests,
+        succeeded,
+        success_rate: succeeded as f64 / requests.max(1) as f64,
+        status_codes,
+        errors,
+        p50_ms: sample_percentile(50.0),
+        p95_ms: sample_percentile(95.0),
+        p99_ms: sample_percentile(99.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_latencies_pools_the_samples_of_the_workers() {
+        let fast = LatencyStats {
+            samples: vec![10.0; 90],
+            p95: 10.0,
+            ..Default::default()
+        };
+        let slow = LatencyStats {
+            samples: vec![100.0; 10],
+            p95: 100.0,
+            ..Default::default()
+        };
+
+        let summary =
+            summarize_latencies(vec![(10.0, &fast), (100.0, &slow)], std::iter::empty(), 1.0)
+                .unwrap();
+
+        assert_eq!(summary.p50_ms, Some(10.0));
+        assert_eq!(summary.p95_ms, Some(100.0));
+        assert_eq!(summary.average_ms, 55.0);
+    }
+
+    #[test]
+    fn summarize_latencies_without_samples() {
+        let summary = summarize_latencies(
+            vec![(0.02, &LatencyStats::default())],
+            std::iter::empty(),
+            1000.0,
+        )
+        .unwrap();
+
+        assert_eq!(summary.p95_ms, None);
+        assert_eq!(summary.average_ms, 20.0);
+    }
+
+    #[test]
+    fn detect_throughput_step_finds_the_drop() {
+        assert_eq!(
+            detect_throughput_step(&[100.0, 100.0, 100.0, 40.0, 40.0, 40.0]),
+            Some(3)
+        );
+        assert_eq!(
+            detect_throughput_step(&[100.0, 95.0, 105.0, 100.0, 30.0, 35.0, 30.0]),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn detect_throughput_step_ignores_small_or_rising_changes() {
+        // Too few windows on each side of a step
+        assert_eq!(detect_throughput_step(&[100.0, 10.0, 10.0]), None);
+        // A drop below the throttling threshold
+        assert_eq!(detect_throughput_step(&[100.0, 100.0, 90.0, 90.0]), None);
+        assert_eq!(detect_throughput_step(&[40.0, 40.0, 100.0, 100.0]), None);
+        assert_eq!(detect_throughput_step(&[0.0, 0.0, 0.0, 0.0]), None);
+    }
+}
@@ -2,3 +2,5 @@ pub mod cancel_job;
 pub mod create_job;
 pub mod get_job;
 pub mod get_jobs;
+pub mod job_summary;
+pub mod rerun_job;
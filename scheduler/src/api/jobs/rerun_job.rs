@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+};
+use axum_extra::extract::WithRejection;
+use common::api_response::*;
+use serde::Deserialize;
+use tracing::{debug, error, info};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    job_repository::{JobDetails, JobStatus},
+    state::AppState,
+};
+
+use super::create_job::{create_sub_jobs, CreateJobResponse};
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct RerunJobPathParams {
+    job_id: Uuid,
+}
+
+/// Create a new job repeating a past job
+#[utoipa::path(
+    post,
+    path = "/jobs/{job_id}/rerun",
+    params (RerunJobPathParams),
+    description = r#"
+**Creates a new Job repeating a past job on the same bytes.**
+
+The new job downloads the same URLs and ranges with the same worker count, topic and settings as the past job, e.g. to verify a fix of the provider. The ranges are not picked again, so the pilot of a job with `auto_size` is not repeated and the range sized by it is downloaded.
+
+Only completed jobs can be re-run. The `rerun_of` of the new job is the ID of the past job.
+"#,
+    responses(
+        (status = 200, description = "Job Created", body = CreateJobResponse),
+        (status = 400, description = "Bad Request", body = ErrorResponse),
+        (status = 404, description = "Not Found", body = ErrorResponse),
+        (status = 500, description = "Internal Server Error", body = ErrorResponse),
+    ),
+    tags = ["Jobs"],
+)]
+#[debug_handler]
+pub async fn handle_rerun_job(
+    WithRejection(Path(params), _): WithRejection<
+        Path<RerunJobPathParams>,
+        ApiResponse<ErrorResponse>,
+    >,
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<CreateJobResponse>, ApiResponse<()>> {
+    let past_job_id = params.job_id;
+
+    info!("Re-running job: {}", past_job_id);
+
+    let past_job = state
+        .repo
+        .job
+        .get_job_by_id(&past_job_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => not_found("Job not found"),
+            _ => {
+                error!("Failed to get job: {:?}", e);
+                bad_request("Failed to get job")
+            }
+        })?;
+
+    // Ranges of unfinished jobs may still change, failed jobs may have measured nothing
+    if !matches!(past_job.status, JobStatus::Completed) {
+        return Err(bad_request("Only completed jobs can be re-run"));
+    }
+
+    // The measured values of the past job are dropped, the ranges stay as they were downloaded
+    let details = JobDetails {
+        workers_count: None,
+        auto_size: None,
+        target_duration_secs: None,
+        pilot_speed: None,
+        next_source: None,
+        rerun_of: Some(past_job_id),
+        ..past_job.details
+    };
+
+    let mut job = state
+        .repo
+        .job
+        .create_job(
+            Uuid::new_v4(),
+            past_job.url,
+            &past_job.routing_key,
+            JobStatus::Pending,
+            details,
+        )
+        .await
+        .map_err(|_| internal_server_error("Failed to create job"))?;
+
+    job.details.redact_headers();
+
+    let sub_jobs = create_sub_jobs(&state, &job).await?;
+
+    debug!(
+        "Job {} re-running job {} created with sub jobs: {:?}",
+        job.id, past_job_id, sub_jobs
+    );
+
+    Ok(ok_response(CreateJobResponse { job, sub_jobs }))
+}
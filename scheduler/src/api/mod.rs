@@ -1,4 +1,5 @@
 pub mod api_doc;
+pub mod credentials;
 pub mod healthcheck;
 pub mod jobs;
 pub mod services;
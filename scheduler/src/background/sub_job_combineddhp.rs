@@ -1,16 +1,21 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use chrono::{Duration, Utc};
 use color_eyre::{
     eyre::{bail, ContextCompat},
     Result,
 };
-use rabbitmq::{JobMessage, Message, Publisher};
-use tracing::{debug, error};
+use rabbitmq::{JobMessage, Message, Publisher, RangeAssignment, WorkerJobType};
+use serde_json::json;
+use tracing::{debug, error, info};
+use uuid::Uuid;
 
 use crate::{
-    job_repository::JobStatus,
-    sub_job_repository::{SubJobStatus, SubJobType, SubJobWithJob},
+    aggregation::{aggregate_downloads, aggregate_interval_logs, WorkerLogs},
+    credentials::request_auth,
+    data_repository::WorkerData,
+    job_repository::{Job, JobStatus},
+    sub_job_repository::{SubJobDetails, SubJobStatus, SubJobType, SubJobWithJob},
     Repositories,
 };
 
@@ -18,11 +23,18 @@ use super::{
     sub_job_handler::SubJobHandlerError, sub_job_scaling::get_workers_online_by_subjob_topic,
 };
 
+// Defaults for jobs created before the timings were configurable
 const MAX_DOWNLOAD_DURATION_SECS: i64 = 60;
 const DOWNLOAD_DELAY_SECS: i64 = 10;
 const SYNC_DELAY_SECS: i64 = 1;
-
-/// Process the sub job with type CombinedDHP
+const SATURATION_GAIN_PERCENT: i64 = 10;
+// Long enough for TCP to ramp up, the pilot only needs an approximate throughput
+const PILOT_DURATION_SECS: i64 = 10;
+const TARGET_DURATION_SECS: i64 = 20;
+const MIN_SIZE_MB: i64 = 10;
+
+/// Process the benchmark sub jobs, CombinedDHP, Upload, Retrieval, Iperf3, RandomAccess, LoadTest,
+/// Soak, the SaturationSearch stages and the Pilot are measured the same way
 pub(super) async fn process_combined_dhp_type(
     repo: Arc<Repositories>,
     job_queue: Arc<Publisher>,
@@ -63,12 +75,31 @@ async fn process_status_created(
     job_queue: Arc<Publisher>,
     sub_job: &SubJobWithJob,
 ) -> Result<(), SubJobHandlerError> {
+    let job = &sub_job.job;
+    let max_duration_secs = match sub_job.r#type {
+        SubJobType::Pilot => PILOT_DURATION_SECS,
+        SubJobType::Soak => job
+            .details
+            .soak_duration_secs
+            .unwrap_or(MAX_DOWNLOAD_DURATION_SECS),
+        _ => job
+            .details
+            .max_duration_secs
+            .unwrap_or(MAX_DOWNLOAD_DURATION_SECS),
+    };
+    let download_delay_secs = job
+        .details
+        .download_delay_secs
+        .unwrap_or(DOWNLOAD_DELAY_SECS);
+    let sync_delay_secs = job.details.sync_delay_secs.unwrap_or(SYNC_DELAY_SECS);
+
     // Calculate the start time for the sub jobs
-    let start_time = Utc::now() + Duration::seconds(SYNC_DELAY_SECS);
-    let download_start_time = start_time + Duration::seconds(DOWNLOAD_DELAY_SECS);
+    let start_time = Utc::now() + Duration::seconds(sync_delay_secs);
+    let download_start_time = start_time + Duration::seconds(download_delay_secs);
 
     let workers_online = get_workers_online_by_subjob_topic(repo.clone(), sub_job).await?;
     let workers_online_total_count = workers_online.len() as i64;
+    let worker_names = workers_online.clone();
 
     if workers_online_total_count == 0 {
         error!("No workers online for sub job: {}", sub_job.id);
@@ -78,13 +109,38 @@ async fn process_status_created(
         ));
     }
 
-    let (excluded_workers, workers_count) = match get_excluded_workers(sub_job, workers_online) {
-        Ok((excluded_workers, workers_count)) => (excluded_workers, workers_count),
-        Err(e) => {
-            debug!("Failed to get excluded workers: {}", e);
-
-            (vec![], workers_online_total_count)
+    let (excluded_workers, workers_count) = match (&sub_job.r#type, &job.details.iperf3_server) {
+        // An iperf3 server instance runs one test at a time, a worker is sent to each of them
+        (SubJobType::Iperf3, Some(iperf3_server)) => {
+            let instances = iperf3_server.port_count as usize;
+            let workers_count = workers_online.len().min(instances) as i64;
+            (
+                workers_online.into_iter().skip(instances).collect(),
+                workers_count,
+            )
         }
+        // A saturation search stage downloads with a given number of workers
+        (SubJobType::SaturationSearch, _) => {
+            let stage_workers = sub_job
+                .details
+                .get("stage_workers")
+                .and_then(|stage_workers| stage_workers.as_u64())
+                .unwrap_or(1) as usize;
+            let workers_count = workers_online.len().min(stage_workers);
+            let excluded_count = workers_online.len() - workers_count;
+            (
+                workers_online.into_iter().take(excluded_count).collect(),
+                workers_count as i64,
+            )
+        }
+        _ => match get_excluded_workers(sub_job, workers_online) {
+            Ok((excluded_workers, workers_count)) => (excluded_workers, workers_count),
+            Err(e) => {
+                debug!("Failed to get excluded workers: {}", e);
+
+                (vec![], workers_online_total_count)
+            }
+        },
     };
 
     repo.sub_job
@@ -92,21 +148,75 @@ async fn process_status_created(
         .await
         .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
 
-    let job = &sub_job.job;
+    let worker_names: Vec<String> = worker_names
+        .into_iter()
+        .filter(|worker_name| !excluded_workers.contains(worker_name))
+        .collect();
+    let assignments = assign_sources(repo.clone(), sub_job, &worker_names).await?;
+
+    let (headers, s3_signing) = request_auth(
+        &repo.credential,
+        job.details.headers.as_ref(),
+        job.details.credential_id.as_ref(),
+        job.details.s3_source.as_ref(),
+    )
+    .await
+    .map_err(|e| SubJobHandlerError::FailedJob(format!("Failed to get the credential: {e}")))?;
+
+    let job_type = match sub_job.r#type {
+        SubJobType::Upload => WorkerJobType::Upload,
+        SubJobType::Retrieval => WorkerJobType::Retrieval,
+        SubJobType::Iperf3 => WorkerJobType::Iperf3,
+        SubJobType::RandomAccess => WorkerJobType::RandomAccess,
+        SubJobType::LoadTest => WorkerJobType::LoadTest,
+        SubJobType::Soak => WorkerJobType::Soak,
+        _ => WorkerJobType::CombinedDHP,
+    };
+
+    // The aggregate request rate of a load test step is split evenly between the workers
+    let request_rate = sub_job
+        .details
+        .get("target_rps")
+        .and_then(|target_rps| target_rps.as_i64())
+        .map(|target_rps| target_rps as f64 / workers_count.max(1) as f64);
 
     let job_message = Message::WorkerJob {
         job_id: job.id,
-        payload: JobMessage {
+        payload: Box::new(JobMessage {
             job_id: job.id,
             sub_job_id: sub_job.id,
+            job_type,
             url: job.url.clone(),
             start_time,
             download_start_time,
             start_range: job.details.start_range,
-            end_range: job.details.end_range,
+            end_range: match sub_job.r#type {
+                SubJobType::Pilot => pilot_end_range(job),
+                _ => job.details.end_range,
+            },
             excluded_workers,
             log_interval_ms: job.details.log_interval_ms,
-        },
+            streams_per_worker: job.details.streams_per_worker.unwrap_or(1),
+            upload_method: job.details.upload_method,
+            max_duration_secs,
+            ping_method: job.details.ping_method,
+            measure_loaded_latency: job.details.measure_loaded_latency.unwrap_or(false),
+            hash_content: job.details.hash_content.unwrap_or(false),
+            verify_piece_cid: job.details.verify_piece_cid.unwrap_or(false),
+            dag_scope: job.details.dag_scope,
+            http_protocol: job.details.http_protocol,
+            connection_policy: job.details.connection_policy,
+            headers,
+            s3_signing,
+            iperf3_server: job.details.iperf3_server.clone(),
+            read_size: job
+                .details
+                .read_size_kib
+                .map(|read_size_kib| read_size_kib * 1024),
+            request_rate,
+            sample_interval_secs: job.details.sample_interval_secs,
+            assignments,
+        }),
     };
 
     debug!("Publishing job message: {:?}", job_message);
@@ -119,7 +229,7 @@ async fn process_status_created(
 
     debug!("Job message published successfully: {}", sub_job.id);
 
-    let deadline_at = download_start_time + Duration::seconds(MAX_DOWNLOAD_DURATION_SECS * 2);
+    let deadline_at = download_start_time + Duration::seconds(max_duration_secs * 2);
 
     repo.sub_job
         .update_sub_job_status_and_deadline(&sub_job.id, SubJobStatus::Pending, deadline_at)
@@ -157,15 +267,29 @@ async fn process_status_processing(
         .map_err(|e| SubJobHandlerError::Skip(format!("Failed to get data: {e}")))?;
 
     if data.len() >= workers_count as usize {
+        // The next stage is created first, the job must not complete in between
+        match sub_job.r#type {
+            SubJobType::SaturationSearch => {
+                continue_saturation_search(repo.clone(), sub_job, workers_count as i64, &data)
+                    .await?
+            }
+            SubJobType::Pilot => apply_pilot_sample_size(repo.clone(), sub_job, &data).await?,
+            SubJobType::CombinedDHP => {
+                store_download_aggregates(repo.clone(), sub_job, &data).await?
+            }
+            _ => {}
+        }
+
         repo.sub_job
             .update_sub_job_status(&sub_job.id, SubJobStatus::Completed)
             .await
             .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
 
-        // Check if all sub jobs are completed
+        // Check if all sub jobs are completed, the pilot and the benchmark subjobs as well as the
+        // iperf3 subjob following them
         let pending_sub_jobs = repo
             .sub_job
-            .count_pending_sub_jobs(SubJobType::CombinedDHP, &sub_job.job_id)
+            .count_pending_sub_jobs(&sub_job.job_id)
             .await
             .map_err(|e| {
                 SubJobHandlerError::Skip(format!("Failed to count pending sub jobs: {e}"))
@@ -189,6 +313,226 @@ async fn process_status_processing(
     Ok(())
 }
 
+/// Interval logs of the downloads of the workers
+fn worker_logs(data: &[WorkerData]) -> Vec<WorkerLogs> {
+    data.iter()
+        .filter_map(|wd| {
+            WorkerLogs::parse(
+                wd.worker_name.clone().unwrap_or_default(),
+                wd.download.get("download_start_time").cloned(),
+                wd.download.get("second_by_second_logs")?.clone(),
+            )
+        })
+        .collect()
+}
+
+/// Aggregate the interval logs of the workers once, the job summary reads the stored aggregates
+/// instead of every log of the job
+async fn store_download_aggregates(
+    repo: Arc<Repositories>,
+    sub_job: &SubJobWithJob,
+    data: &[WorkerData],
+) -> Result<(), SubJobHandlerError> {
+    let download_aggregates =
+        aggregate_downloads(&worker_logs(data), sub_job.job.details.log_interval_ms);
+
+    repo.sub_job
+        .update_sub_job_download_aggregates(
+            &sub_job.id,
+            serde_json::to_value(download_aggregates).unwrap_or_default(),
+        )
+        .await
+        .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Last byte of the pilot range, the pilot downloads from the first half of the job range so the
+/// benchmark range in the second half holds no bytes cached by the pilot
+fn pilot_end_range(job: &Job) -> i64 {
+    job.details.start_range + (job.details.end_range - job.details.start_range) / 2
+}
+
+/// Shrink the range of the job so each worker downloads for about the target duration at the
+/// speed measured by the pilot
+///
+/// The pilot runs with every worker, so the full benchmark subjob takes about the target duration
+/// and the partial ones finish earlier. Without a pilot speed the requested size is kept. The
+/// benchmark range starts right after the pilot range.
+async fn apply_pilot_sample_size(
+    repo: Arc<Repositories>,
+    sub_job: &SubJobWithJob,
+    data: &[WorkerData],
+) -> Result<(), SubJobHandlerError> {
+    let job = &sub_job.job;
+
+    let speeds: Vec<f64> = data
+        .iter()
+        .filter_map(|wd| wd.download.get("download_speed")?.as_f64())
+        .filter(|speed| *speed > 0.0)
+        .collect();
+    let pilot_speed =
+        Some(speeds.iter().sum::<f64>() / speeds.len() as f64).filter(|_| !speeds.is_empty());
+
+    // The benchmark range is cut from the part of the largest range the pilot left untouched
+    let start_range = pilot_end_range(job) + 1;
+    let max_size_mb = (job.details.end_range - start_range) / (1024 * 1024);
+    let target_duration_secs = job
+        .details
+        .target_duration_secs
+        .unwrap_or(TARGET_DURATION_SECS);
+    let size_mb = match pilot_speed {
+        // Speeds are in megabits per second
+        Some(speed) => (speed / 8.0 * target_duration_secs as f64).round() as i64,
+        None => {
+            error!("Pilot of job {} measured no download speed", job.id);
+
+            job.details.size_mb
+        }
+    }
+    .clamp(MIN_SIZE_MB.min(max_size_mb), max_size_mb);
+
+    info!(
+        "Pilot of job {} measured {:?} Mbps per worker, sample size set to {} MB",
+        job.id, pilot_speed, size_mb
+    );
+
+    repo.job
+        .update_job_sample_size(
+            &job.id,
+            start_range,
+            start_range + size_mb * 1024 * 1024,
+            size_mb,
+            pilot_speed,
+        )
+        .await
+        .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Start the next stage of the saturation search with twice the workers, unless the aggregate
+/// throughput gain of the stage fell below the threshold or every worker was already used
+async fn continue_saturation_search(
+    repo: Arc<Repositories>,
+    sub_job: &SubJobWithJob,
+    workers_count: i64,
+    data: &[WorkerData],
+) -> Result<(), SubJobHandlerError> {
+    let job = &sub_job.job;
+
+    // The speed over the intervals all workers were downloading, the sum of the worker speeds
+    // when the downloads didn't overlap
+    let (overlap, _) = aggregate_interval_logs(&worker_logs(data), job.details.log_interval_ms);
+    let aggregate_speed = overlap.map(|overlap| overlap.speed).unwrap_or_else(|| {
+        data.iter()
+            .filter_map(|wd| wd.download.get("download_speed")?.as_f64())
+            .sum()
+    });
+    repo.sub_job
+        .update_sub_job_aggregate_speed(&sub_job.id, aggregate_speed)
+        .await
+        .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
+
+    let gain_percent = sub_job
+        .details
+        .get("previous_speed")
+        .and_then(|previous_speed| previous_speed.as_f64())
+        .filter(|previous_speed| *previous_speed > 0.0)
+        .map(|previous_speed| (aggregate_speed - previous_speed) / previous_speed * 100.0);
+    let min_gain_percent = job
+        .details
+        .saturation_gain_percent
+        .unwrap_or(SATURATION_GAIN_PERCENT) as f64;
+    let workers_online = get_workers_online_by_subjob_topic(repo.clone(), sub_job)
+        .await?
+        .len() as i64;
+
+    if gain_percent.is_some_and(|gain_percent| gain_percent < min_gain_percent)
+        || workers_count >= workers_online
+    {
+        info!(
+            "Saturation search of job {} finished with {} workers at {:.2} Mbps",
+            job.id, workers_count, aggregate_speed
+        );
+
+        // The raw TCP throughput is measured after the search, both would share the network
+        if job.details.iperf3_server.is_some() {
+            repo.sub_job
+                .create_sub_job_once(
+                    Uuid::new_v4(),
+                    job.id,
+                    SubJobStatus::Created,
+                    SubJobType::Iperf3,
+                    SubJobDetails::empty(),
+                    json!({}),
+                )
+                .await
+                .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
+        }
+
+        return Ok(());
+    }
+
+    let stage_workers = (workers_count * 2).min(workers_online);
+    debug!(
+        "Saturation search stage reached {:.2} Mbps with {} workers, next stage with {} workers",
+        aggregate_speed, workers_count, stage_workers
+    );
+
+    // The stages double the workers, so the worker count identifies the stage
+    repo.sub_job
+        .create_sub_job_once(
+            Uuid::new_v4(),
+            job.id,
+            SubJobStatus::Created,
+            SubJobType::SaturationSearch,
+            SubJobDetails::saturation_stage(stage_workers, Some(aggregate_speed)),
+            json!({ "stage_workers": stage_workers }),
+        )
+        .await
+        .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Assign the sources of a multi-URL job to the workers, continuing the rotation of the previous
+/// subjob so the later subjobs also read sources no worker read before
+async fn assign_sources(
+    repo: Arc<Repositories>,
+    sub_job: &SubJobWithJob,
+    worker_names: &[String],
+) -> Result<BTreeMap<String, RangeAssignment>, SubJobHandlerError> {
+    let sources = match (&sub_job.r#type, &sub_job.job.details.sources) {
+        (SubJobType::CombinedDHP, Some(sources)) if !sources.is_empty() => sources,
+        _ => return Ok(BTreeMap::new()),
+    };
+
+    let next_source = sub_job.job.details.next_source.unwrap_or(0).max(0) as usize;
+    let source_indexes: BTreeMap<&String, usize> = worker_names
+        .iter()
+        .enumerate()
+        .map(|(i, worker_name)| (worker_name, (next_source + i) % sources.len()))
+        .collect();
+
+    repo.sub_job
+        .update_sub_job_assignments(&sub_job.id, json!(source_indexes))
+        .await
+        .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
+    repo.job
+        .update_job_next_source(
+            &sub_job.job.id,
+            ((next_source + worker_names.len()) % sources.len()) as i64,
+        )
+        .await
+        .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
+
+    Ok(source_indexes
+        .into_iter()
+        .map(|(worker_name, source)| (worker_name.clone(), sources[source].clone()))
+        .collect())
+}
+
 fn check_deadline(sub_job: &SubJobWithJob) -> Result<(), SubJobHandlerError> {
     let deadline = sub_job
         .deadline_at
@@ -49,7 +49,15 @@ pub async fn sub_job_handler(
         debug!("Found sub job: {:?}", sub_job);
 
         let _ = match sub_job.r#type {
-            SubJobType::CombinedDHP => {
+            SubJobType::CombinedDHP
+            | SubJobType::Upload
+            | SubJobType::Retrieval
+            | SubJobType::Iperf3
+            | SubJobType::RandomAccess
+            | SubJobType::LoadTest
+            | SubJobType::SaturationSearch
+            | SubJobType::Pilot
+            | SubJobType::Soak => {
                 process_combined_dhp_type(repo.clone(), job_queue.clone(), sub_job).await
             }
             SubJobType::Scaling => {
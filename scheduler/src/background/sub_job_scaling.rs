@@ -14,6 +14,7 @@ use crate::{
 
 use super::sub_job_handler::SubJobHandlerError;
 
+// Defaults for jobs created before the deadlines were configurable
 const SERVICE_DESCALE_AT_DEADLINE_SEC: u64 = 1800; // 0.5h
 const SCALING_SUB_JOB_DEADLINE_SEC: u64 = SERVICE_DESCALE_AT_DEADLINE_SEC - 300; // 5 minutes before service deadline
 
@@ -69,7 +70,12 @@ async fn process_scaling_created(
         .map_err(|e| SubJobHandlerError::Skip(e.to_string()))?;
 
     // Extend the descale_at deadline for all services
-    let descale_at = Utc::now() + Duration::from_secs(SERVICE_DESCALE_AT_DEADLINE_SEC);
+    let descale_deadline_secs = sub_job
+        .job
+        .details
+        .descale_deadline_secs
+        .map_or(SERVICE_DESCALE_AT_DEADLINE_SEC, |secs| secs as u64);
+    let descale_at = Utc::now() + Duration::from_secs(descale_deadline_secs);
     repo.service
         .set_descale_deadlines(&services.iter().map(|s| s.id).collect(), descale_at)
         .await
@@ -112,7 +118,12 @@ async fn process_scaling_created(
     }
 
     // Update sub job status to processing
-    let deadline_at = Utc::now() + Duration::from_secs(SCALING_SUB_JOB_DEADLINE_SEC);
+    let scaling_deadline_secs = sub_job
+        .job
+        .details
+        .scaling_deadline_secs
+        .map_or(SCALING_SUB_JOB_DEADLINE_SEC, |secs| secs as u64);
+    let deadline_at = Utc::now() + Duration::from_secs(scaling_deadline_secs);
     repo.sub_job
         .update_sub_job_status_and_deadline(&sub_job.id, SubJobStatus::Processing, deadline_at)
         .await
@@ -13,6 +13,8 @@ pub struct Config {
     pub log_level: String,
     pub auth_token: String,
     pub local_mode: String,
+    /// Base64 encoded 256 bit key encrypting the stored credentials
+    pub credentials_key: Option<String>,
 }
 impl Config {
     pub fn new_from_env() -> Result<Self> {
@@ -33,6 +35,7 @@ impl Config {
             auth_token: env::var("AUTH_TOKEN")
                 .unwrap_or("mysecrettokenthatdefinatelyisnotongithubpublicrepo".to_string()),
             local_mode: env::var("LOCAL_MODE").unwrap_or("false".to_string()),
+            credentials_key: env::var("CREDENTIALS_KEY").ok(),
         })
     }
 }
@@ -0,0 +1,210 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{
+    eyre::{bail, eyre, ContextCompat},
+    Result,
+};
+use rabbitmq::{RequestHeaders, S3Signing};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    config::CONFIG,
+    credential_repository::{CredentialRepository, EncryptedCredential},
+    job_repository::S3Source,
+};
+
+/// Secret used to authenticate against the target, sent as a request header or signing the
+/// requests to an S3 source
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum CredentialSecret {
+    Bearer {
+        token: String,
+    },
+    Basic {
+        username: String,
+        password: String,
+    },
+    Header {
+        name: String,
+        value: String,
+    },
+    S3 {
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: Option<String>,
+    },
+}
+
+impl CredentialSecret {
+    /// Name and value of the request header, S3 access keys sign the requests instead
+    pub fn header(&self) -> Option<(String, String)> {
+        match self {
+            CredentialSecret::Bearer { token } => {
+                Some(("Authorization".to_string(), format!("Bearer {token}")))
+            }
+            CredentialSecret::Basic { username, password } => Some((
+                "Authorization".to_string(),
+                format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{username}:{password}"))
+                ),
+            )),
+            CredentialSecret::Header { name, value } => Some((name.clone(), value.clone())),
+            CredentialSecret::S3 { .. } => None,
+        }
+    }
+
+    /// Signing parameters of the S3 source, only S3 access keys can sign
+    pub fn s3_signing(&self, source: &S3Source) -> Result<S3Signing> {
+        match self {
+            CredentialSecret::S3 {
+                access_key_id,
+                secret_access_key,
+                session_token,
+            } => Ok(S3Signing {
+                region: source.region.clone(),
+                access_key_id: access_key_id.clone(),
+                secret_access_key: secret_access_key.clone(),
+                session_token: session_token.clone(),
+            }),
+            _ => bail!("S3 source requires an S3 credential"),
+        }
+    }
+}
+
+fn key() -> Result<LessSafeKey> {
+    let encoded = CONFIG
+        .credentials_key
+        .as_ref()
+        .context("CREDENTIALS_KEY is not set")?;
+    let key = STANDARD.decode(encoded)?;
+    let key = UnboundKey::new(&AES_256_GCM, &key)
+        .map_err(|_| eyre!("CREDENTIALS_KEY must be 32 bytes"))?;
+
+    Ok(LessSafeKey::new(key))
+}
+
+/// Encrypt with AES-256-GCM, the associated data is authenticated with the ciphertext
+fn seal(aad: &[u8], mut plaintext: Vec<u8>) -> Result<EncryptedCredential> {
+    let key = key()?;
+
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| eyre!("Failed to generate nonce"))?;
+
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut plaintext,
+    )
+    .map_err(|_| eyre!("Failed to encrypt"))?;
+
+    Ok(EncryptedCredential {
+        nonce: nonce.to_vec(),
+        ciphertext: plaintext,
+    })
+}
+
+fn open(aad: &[u8], encrypted: EncryptedCredential) -> Result<Vec<u8>> {
+    let key = key()?;
+
+    let nonce =
+        Nonce::try_assume_unique_for_key(&encrypted.nonce).map_err(|_| eyre!("Invalid nonce"))?;
+    let mut ciphertext = encrypted.ciphertext;
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(aad), &mut ciphertext)
+        .map_err(|_| eyre!("Failed to decrypt"))?;
+
+    Ok(plaintext.to_vec())
+}
+
+/// Encrypt the secret with AES-256-GCM, the credential id is authenticated with it
+pub fn encrypt(credential_id: &Uuid, secret: &CredentialSecret) -> Result<EncryptedCredential> {
+    seal(credential_id.as_bytes(), serde_json::to_vec(secret)?)
+}
+
+pub fn decrypt(credential_id: &Uuid, encrypted: EncryptedCredential) -> Result<CredentialSecret> {
+    Ok(serde_json::from_slice(&open(
+        credential_id.as_bytes(),
+        encrypted,
+    )?)?)
+}
+
+/// Encrypt the values of the custom headers like the credentials, the header name is
+/// authenticated with its value, which is stored as the base64 of the nonce and the ciphertext
+pub fn encrypt_headers(headers: &RequestHeaders) -> Result<RequestHeaders> {
+    let mut encrypted_headers = RequestHeaders::default();
+    for (name, value) in &headers.0 {
+        let encrypted = seal(name.as_bytes(), value.as_bytes().to_vec())?;
+        encrypted_headers.0.insert(
+            name.clone(),
+            STANDARD.encode([encrypted.nonce, encrypted.ciphertext].concat()),
+        );
+    }
+
+    Ok(encrypted_headers)
+}
+
+fn decrypt_headers(headers: &RequestHeaders) -> Result<RequestHeaders> {
+    let mut decrypted_headers = RequestHeaders::default();
+    for (name, value) in &headers.0 {
+        let encrypted = STANDARD.decode(value)?;
+        if encrypted.len() < NONCE_LEN {
+            bail!("Invalid encrypted value of header {name}");
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let value = open(
+            name.as_bytes(),
+            EncryptedCredential {
+                nonce: nonce.to_vec(),
+                ciphertext: ciphertext.to_vec(),
+            },
+        )?;
+        decrypted_headers
+            .0
+            .insert(name.clone(), String::from_utf8(value)?);
+    }
+
+    Ok(decrypted_headers)
+}
+
+/// Authentication of the requests to the target, the decrypted custom headers with the header of
+/// the credential and the signing parameters of an S3 source
+pub async fn request_auth(
+    repo: &CredentialRepository,
+    headers: Option<&RequestHeaders>,
+    credential_id: Option<&Uuid>,
+    s3_source: Option<&S3Source>,
+) -> Result<(RequestHeaders, Option<S3Signing>)> {
+    let mut request_headers = headers
+        .map(decrypt_headers)
+        .transpose()?
+        .unwrap_or_default();
+
+    let secret = match credential_id {
+        Some(credential_id) => {
+            let encrypted = repo.get_encrypted_credential(credential_id).await?;
+            Some(decrypt(credential_id, encrypted)?)
+        }
+        None => None,
+    };
+
+    if let Some((name, value)) = secret.as_ref().and_then(CredentialSecret::header) {
+        request_headers.0.insert(name, value);
+    }
+
+    let s3_signing = match (s3_source, &secret) {
+        (Some(source), Some(secret)) => Some(secret.s3_signing(source)?),
+        (Some(_), None) => bail!("S3 source requires a credential"),
+        (None, _) => None,
+    };
+
+    Ok((request_headers, s3_signing))
+}
@@ -25,9 +25,11 @@ use tracing_subscriber::EnvFilter;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+mod aggregation;
 mod api;
 mod background;
 mod config;
+mod credentials;
 mod queue;
 mod repository;
 mod routes;
@@ -0,0 +1,9 @@
+-- Remove upload column from worker_data table
+ALTER TABLE worker_data DROP COLUMN upload;
+
+-- Remove upload sub_jobs type enum value
+DELETE FROM sub_jobs WHERE type = 'Upload';
+CREATE TYPE sub_job_type_new AS ENUM ('CombinedDHP', 'Scaling');
+ALTER TABLE sub_jobs ALTER COLUMN type TYPE sub_job_type_new USING type::text::sub_job_type_new;
+DROP TYPE sub_job_type;
+ALTER TYPE sub_job_type_new RENAME TO sub_job_type;
@@ -0,0 +1,5 @@
+-- Add upload sub_jobs type enum value
+ALTER TYPE sub_job_type ADD VALUE 'Upload';
+
+-- Add upload column to worker_data table
+ALTER TABLE worker_data ADD COLUMN upload JSONB;
@@ -0,0 +1,5 @@
+-- Add retrieval sub_jobs type enum value
+ALTER TYPE sub_job_type ADD VALUE 'Retrieval';
+
+-- Add retrieval column to worker_data table
+ALTER TABLE worker_data ADD COLUMN retrieval JSONB;
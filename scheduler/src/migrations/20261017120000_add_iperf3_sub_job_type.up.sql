@@ -0,0 +1,5 @@
+-- Add iperf3 sub_jobs type enum value
+ALTER TYPE sub_job_type ADD VALUE 'Iperf3';
+
+-- Add iperf3 column to worker_data table
+ALTER TABLE worker_data ADD COLUMN iperf3 JSONB;
@@ -0,0 +1,5 @@
+-- Add random access sub_jobs type enum value
+ALTER TYPE sub_job_type ADD VALUE 'RandomAccess';
+
+-- Add random_access column to worker_data table
+ALTER TABLE worker_data ADD COLUMN random_access JSONB;
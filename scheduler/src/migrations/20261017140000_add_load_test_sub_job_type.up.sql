@@ -0,0 +1,5 @@
+-- Add load test sub_jobs type enum value
+ALTER TYPE sub_job_type ADD VALUE 'LoadTest';
+
+-- Add load_test column to worker_data table
+ALTER TABLE worker_data ADD COLUMN load_test JSONB;
@@ -0,0 +1,6 @@
+-- Remove pilot sub_jobs type enum value
+DELETE FROM sub_jobs WHERE type = 'Pilot';
+CREATE TYPE sub_job_type_new AS ENUM ('CombinedDHP', 'Scaling', 'Upload', 'Retrieval', 'Iperf3', 'RandomAccess', 'LoadTest', 'SaturationSearch');
+ALTER TABLE sub_jobs ALTER COLUMN type TYPE sub_job_type_new USING type::text::sub_job_type_new;
+DROP TYPE sub_job_type;
+ALTER TYPE sub_job_type_new RENAME TO sub_job_type;
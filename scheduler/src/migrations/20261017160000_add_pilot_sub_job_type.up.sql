@@ -0,0 +1,2 @@
+-- Add pilot sub_jobs type enum value
+ALTER TYPE sub_job_type ADD VALUE 'Pilot';
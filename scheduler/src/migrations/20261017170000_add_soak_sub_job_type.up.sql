@@ -0,0 +1,5 @@
+-- Add soak sub_jobs type enum value
+ALTER TYPE sub_job_type ADD VALUE 'Soak';
+
+-- Add soak column to worker_data table
+ALTER TABLE worker_data ADD COLUMN soak JSONB;
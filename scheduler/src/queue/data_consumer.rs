@@ -26,7 +26,7 @@ impl DataConsumer {
 
     async fn parse_message(&self, content_str: &str) -> Result<(Uuid, ResultMessage)> {
         match serde_json::from_str::<Message>(content_str) {
-            Ok(Message::WorkerResult { job_id, result }) => Ok((job_id, result)),
+            Ok(Message::WorkerResult { job_id, result }) => Ok((job_id, *result)),
             Ok(_) => Err(eyre!("Received unexpected message")),
             Err(e) => {
                 error!("Error parsing message: {:?}", e);
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgPool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Stored credential, the secret never leaves the repository unencrypted
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct Credential {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct EncryptedCredential {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+pub struct CredentialRepository {
+    pool: PgPool,
+}
+
+impl CredentialRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_credential(
+        &self,
+        id: &Uuid,
+        name: &str,
+        encrypted: &EncryptedCredential,
+    ) -> Result<Credential, sqlx::Error> {
+        let credential = sqlx::query_as!(
+            Credential,
+            r#"
+            INSERT INTO credentials (id, name, nonce, ciphertext)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, created_at
+            "#,
+            id,
+            name,
+            encrypted.nonce,
+            encrypted.ciphertext,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(credential)
+    }
+
+    pub async fn get_credentials(&self) -> Result<Vec<Credential>, sqlx::Error> {
+        let credentials = sqlx::query_as!(
+            Credential,
+            r#"
+            SELECT id, name, created_at
+            FROM credentials
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(credentials)
+    }
+
+    pub async fn get_encrypted_credential(
+        &self,
+        credential_id: &Uuid,
+    ) -> Result<EncryptedCredential, sqlx::Error> {
+        let encrypted = sqlx::query_as!(
+            EncryptedCredential,
+            r#"
+            SELECT nonce, ciphertext
+            FROM credentials
+            WHERE id = $1
+            "#,
+            credential_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(encrypted)
+    }
+
+    pub async fn delete_credential_by_id(
+        &self,
+        credential_id: &Uuid,
+    ) -> Result<Credential, sqlx::Error> {
+        let credential = sqlx::query_as!(
+            Credential,
+            r#"
+            DELETE FROM credentials
+            WHERE id = $1
+            RETURNING id, name, created_at
+            "#,
+            credential_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(credential)
+    }
+}
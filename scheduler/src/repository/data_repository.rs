@@ -19,6 +19,31 @@ pub struct WorkerData {
     pub head: serde_json::Value,
 }
 
+/// Interval logs of a successful download
+#[derive(Debug)]
+pub struct DownloadLogs {
+    pub sub_job_id: Option<Uuid>,
+    pub worker_name: Option<String>,
+    pub download_start_time: Option<serde_json::Value>,
+    pub logs: Option<serde_json::Value>,
+}
+
+/// Latency samples of a load test
+#[derive(Debug)]
+pub struct LoadTestSamples {
+    pub sub_job_id: Option<Uuid>,
+    pub samples: Option<serde_json::Value>,
+}
+
+/// Latency samples of the ping, head and random access measurements of a worker
+#[derive(Debug)]
+pub struct LatencySamples {
+    pub id: Uuid,
+    pub ping: Option<serde_json::Value>,
+    pub head: Option<serde_json::Value>,
+    pub ttfb: Option<serde_json::Value>,
+}
+
 impl DataRepository {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
@@ -45,9 +70,15 @@ impl DataRepository {
                 is_success,
                 download,
                 ping,
-                head
+                head,
+                upload,
+                retrieval,
+                iperf3,
+                random_access,
+                load_test,
+                soak
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
             "#,
             result.run_id,
             result.job_id,
@@ -56,7 +87,13 @@ impl DataRepository {
             result.is_success,
             self.result_to_json(result.download_result),
             self.result_to_json(result.ping_result),
-            self.result_to_json(result.head_result)
+            self.result_to_json(result.head_result),
+            result.upload_result.map(|r| self.result_to_json(r)),
+            result.retrieval_result.map(|r| self.result_to_json(r)),
+            result.iperf3_result.map(|r| self.result_to_json(r)),
+            result.random_access_result.map(|r| self.result_to_json(r)),
+            result.load_test_result.map(|r| self.result_to_json(r)),
+            result.soak_result.map(|r| self.result_to_json(r)),
         )
         .execute(&self.pool)
         .await?;
@@ -82,4 +119,71 @@ impl DataRepository {
 
         Ok(data)
     }
+
+    pub async fn get_download_logs_by_sub_job_ids(
+        &self,
+        sub_job_ids: &[Uuid],
+    ) -> Result<Vec<DownloadLogs>, sqlx::Error> {
+        let data = sqlx::query_as!(
+            DownloadLogs,
+            r#"
+            SELECT
+                sub_job_id,
+                worker_name,
+                download -> 'download_start_time' AS download_start_time,
+                download -> 'second_by_second_logs' AS logs
+            FROM worker_data
+            WHERE sub_job_id = ANY($1) AND download ? 'second_by_second_logs'
+            "#,
+            sub_job_ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(data)
+    }
+
+    pub async fn get_load_test_samples_by_job_id(
+        &self,
+        job_id: &Uuid,
+    ) -> Result<Vec<LoadTestSamples>, sqlx::Error> {
+        let data = sqlx::query_as!(
+            LoadTestSamples,
+            r#"
+            SELECT
+                sub_job_id,
+                load_test -> 'latency_ms' -> 'samples' AS samples
+            FROM worker_data
+            WHERE job_id = $1 AND load_test ? 'latency_ms'
+            "#,
+            job_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(data)
+    }
+
+    pub async fn get_latency_samples_by_job_id(
+        &self,
+        job_id: &Uuid,
+    ) -> Result<Vec<LatencySamples>, sqlx::Error> {
+        let data = sqlx::query_as!(
+            LatencySamples,
+            r#"
+            SELECT
+                id,
+                ping -> 'samples' AS ping,
+                head -> 'samples' AS head,
+                random_access -> 'ttfb_ms' -> 'samples' AS ttfb
+            FROM worker_data
+            WHERE job_id = $1
+            "#,
+            job_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(data)
+    }
 }
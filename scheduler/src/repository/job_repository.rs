@@ -1,5 +1,9 @@
 use chrono::{DateTime, Utc};
 use color_eyre::Result;
+use rabbitmq::{
+    ConnectionPolicy, DagScope, HttpProtocol, Iperf3Server, PingMethod, RangeAssignment,
+    RequestHeaders, UploadMethod,
+};
 use serde::{Deserialize, Serialize};
 use sqlx::{
     prelude::{FromRow, Type},
@@ -11,6 +15,8 @@ use uuid::Uuid;
 
 use crate::sub_job_repository::{SubJob, SubJobStatus, SubJobType};
 
+const REDACTED: &str = "[REDACTED]";
+
 #[derive(Debug, Type, Serialize, Deserialize, ToSchema)]
 #[sqlx(type_name = "job_status")]
 pub enum JobStatus {
@@ -22,6 +28,17 @@ pub enum JobStatus {
     Canceled,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Default)]
+pub enum JobType {
+    #[default]
+    Download,
+    Upload,
+    Retrieval,
+    RandomAccess,
+    LoadTest,
+    Soak,
+}
+
 #[derive(Clone)]
 pub struct JobRepository {
     pool: PgPool,
@@ -68,6 +85,18 @@ pub struct WorkerData {
     pub download: serde_json::Value,
     pub ping: serde_json::Value,
     pub head: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retrieval: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iperf3: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub random_access: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_test: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soak: Option<serde_json::Value>,
 }
 
 #[allow(dead_code)]
@@ -89,7 +118,7 @@ pub struct WorkerDataError {
     error: String,
 }
 
-#[derive(Serialize, Deserialize, FromRow, Type, Debug, ToSchema)]
+#[derive(Serialize, Deserialize, Debug, ToSchema, Default)]
 pub struct JobDetails {
     pub start_range: i64,
     pub end_range: i64,
@@ -101,26 +130,115 @@ pub struct JobDetails {
     pub note: Option<String>,
     pub log_interval_ms: i64,
     pub size_mb: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub streams_per_worker: Option<i64>,
+    #[serde(default)]
+    pub job_type: JobType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "Put")]
+    pub upload_method: Option<UploadMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_duration_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_delay_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_delay_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scaling_deadline_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descale_deadline_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "Tcp")]
+    pub ping_method: Option<PingMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub measure_loaded_latency: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash_content: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_piece_cid: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "All")]
+    pub dag_scope: Option<DagScope>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "Http2")]
+    pub http_protocol: Option<HttpProtocol>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "Reuse")]
+    pub connection_policy: Option<ConnectionPolicy>,
+    /// Custom request headers, the values are encrypted and redacted in the API responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub headers: Option<RequestHeaders>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_source: Option<S3Source>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub iperf3_server: Option<Iperf3Server>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_size_kib: Option<i64>,
+    /// Aggregate request rates of the load test subjobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_rps: Option<Vec<i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation_search: Option<bool>,
+    /// Minimum aggregate throughput gain of a saturation search stage to add more workers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation_gain_percent: Option<i64>,
+    /// Pick `size_mb` from the throughput measured by a pilot subjob
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_size: Option<bool>,
+    /// Download duration the automatic sample size aims for on each worker
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_duration_secs: Option<i64>,
+    /// Average download speed of the workers in the pilot subjob
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pilot_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soak_duration_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_interval_secs: Option<i64>,
+    /// Every URL of a multi-URL job with its range, the first one is the URL of the job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub sources: Option<Vec<RangeAssignment>>,
+    /// Source assigned to the first worker of the next benchmark subjob, the sources rotate over
+    /// the subjobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_source: Option<i64>,
+    /// Seed of the random range selection, a job created with it picks the same ranges of an
+    /// unchanged file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Job this job repeats on the same URLs and ranges
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerun_of: Option<Uuid>,
+}
+
+/// Object in an S3 compatible storage, e.g. AWS S3 or MinIO, the requests are signed with SigV4
+#[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
+pub struct S3Source {
+    /// Endpoint of the storage, the object is addressed path style
+    #[schema(example = "https://s3.us-east-1.amazonaws.com")]
+    pub endpoint: String,
+    #[schema(example = "us-east-1")]
+    pub region: String,
+    #[schema(example = "my-bucket")]
+    pub bucket: String,
+    #[schema(example = "test.zip")]
+    pub key: String,
 }
+
 impl JobDetails {
-    pub fn new(
-        start_range: i64,
-        end_range: i64,
-        target_worker_count: i64,
-        entity: Option<String>,
-        note: Option<String>,
-        log_interval_ms: i64,
-        size_mb: i64,
-    ) -> Self {
-        JobDetails {
-            start_range,
-            end_range,
-            target_worker_count: Some(target_worker_count),
-            workers_count: None,
-            entity,
-            note,
-            log_interval_ms,
-            size_mb,
+    /// Hide the header values, they may hold secrets of the target
+    pub fn redact_headers(&mut self) {
+        for value in self
+            .headers
+            .iter_mut()
+            .flat_map(|headers| headers.0.values_mut())
+        {
+            *value = REDACTED.to_string();
         }
     }
 }
@@ -183,7 +301,6 @@ impl JobRepository {
         Ok(job)
     }
 
-    #[allow(dead_code)]
     pub async fn get_job_by_id(&self, job_id: &Uuid) -> Result<Job, sqlx::Error> {
         let job = sqlx::query_as!(
             Job,
@@ -239,6 +356,57 @@ impl JobRepository {
         Ok(())
     }
 
+    pub async fn update_job_sample_size(
+        &self,
+        job_id: &Uuid,
+        start_range: i64,
+        end_range: i64,
+        size_mb: i64,
+        pilot_speed: Option<f64>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE jobs
+            SET details = details || jsonb_build_object(
+                'start_range', $2::bigint,
+                'end_range', $3::bigint,
+                'size_mb', $4::bigint,
+                'pilot_speed', $5::float8
+            )
+            WHERE id = $1
+            "#,
+            job_id,
+            start_range,
+            end_range,
+            size_mb,
+            pilot_speed,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_job_next_source(
+        &self,
+        job_id: &Uuid,
+        next_source: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE jobs
+            SET details = details || jsonb_build_object('next_source', $2::bigint)
+            WHERE id = $1
+            "#,
+            job_id,
+            next_source,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_job_by_id_with_subjobs_and_data(
         &self,
         job_id: Uuid,
@@ -275,9 +443,20 @@ impl JobRepository {
                             'id', d.id,
                             'worker_name', d.worker_name,
                             'is_success', COALESCE(d.is_success, false),
-                            'download', CASE WHEN $2 THEN d.download ELSE d.download - 'second_by_second_logs' END,
-                            'ping', d.ping,
-                            'head', d.head
+                            'download', CASE WHEN $2 THEN d.download ELSE (d.download - ARRAY['second_by_second_logs', 'tcp_info_samples']) || COALESCE((
+                                -- Every parallel stream carries logs of its own
+                                SELECT JSONB_BUILD_OBJECT('streams', JSONB_AGG(s.stream - ARRAY['second_by_second_logs', 'tcp_info_samples'] ORDER BY s.index))
+                                FROM JSONB_ARRAY_ELEMENTS(d.download -> 'streams') WITH ORDINALITY AS s(stream, index)
+                                HAVING COUNT(*) > 0
+                            ), '{}') END,
+                            'ping', CASE WHEN $2 THEN d.ping ELSE d.ping - 'samples' END,
+                            'head', CASE WHEN $2 THEN d.head ELSE d.head - 'samples' END,
+                            'upload', CASE WHEN $2 THEN d.upload ELSE d.upload - 'second_by_second_logs' END,
+                            'retrieval', CASE WHEN $2 THEN d.retrieval ELSE d.retrieval - 'second_by_second_logs' END,
+                            'iperf3', CASE WHEN $2 THEN d.iperf3 ELSE d.iperf3 - 'second_by_second_logs' END,
+                            'random_access', CASE WHEN $2 THEN d.random_access ELSE d.random_access #- '{ttfb_ms,samples}' END,
+                            'load_test', CASE WHEN $2 THEN d.load_test ELSE d.load_test - 'per_second' #- '{latency_ms,samples}' END,
+                            'soak', d.soak
                         )
                         ORDER BY d.created_at ASC
                     ) AS "worker_data"
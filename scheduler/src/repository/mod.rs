@@ -1,3 +1,4 @@
+pub mod credential_repository;
 pub mod data_repository;
 pub mod job_repository;
 pub mod service_repository;
@@ -7,6 +8,7 @@ pub mod worker_repository;
 
 use sqlx::PgPool;
 
+pub use self::credential_repository::CredentialRepository;
 pub use self::data_repository::DataRepository;
 pub use self::job_repository::JobRepository;
 pub use self::service_repository::ServiceRepository;
@@ -15,6 +17,7 @@ pub use self::topic_repository::TopicRepository;
 pub use self::worker_repository::WorkerRepository;
 
 pub struct Repositories {
+    pub credential: CredentialRepository,
     pub data: DataRepository,
     pub job: JobRepository,
     pub service: ServiceRepository,
@@ -26,6 +29,7 @@ pub struct Repositories {
 impl Repositories {
     pub fn new(pool: PgPool) -> Self {
         Self {
+            credential: CredentialRepository::new(pool.clone()),
             data: DataRepository::new(pool.clone()),
             job: JobRepository::new(pool.clone()),
             service: ServiceRepository::new(pool.clone()),
@@ -27,6 +27,14 @@ pub enum SubJobStatus {
 pub enum SubJobType {
     CombinedDHP,
     Scaling,
+    Upload,
+    Retrieval,
+    Iperf3,
+    RandomAccess,
+    LoadTest,
+    SaturationSearch,
+    Pilot,
+    Soak,
 }
 
 #[derive(Clone)]
@@ -77,6 +85,15 @@ pub struct SubJobDetails {
     pub workers_count: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub topic: Option<String>,
+    /// Aggregate request rate of all workers in a load test subjob
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_rps: Option<i64>,
+    /// Number of workers of a saturation search stage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage_workers: Option<i64>,
+    /// Aggregate download speed of the previous saturation search stage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_speed: Option<f64>,
 }
 impl SubJobDetails {
     pub fn empty() -> Self {
@@ -90,6 +107,19 @@ impl SubJobDetails {
             ..Default::default()
         }
     }
+    pub fn load_test(target_rps: i64) -> Self {
+        SubJobDetails {
+            target_rps: Some(target_rps),
+            ..Default::default()
+        }
+    }
+    pub fn saturation_stage(stage_workers: i64, previous_speed: Option<f64>) -> Self {
+        SubJobDetails {
+            stage_workers: Some(stage_workers),
+            previous_speed,
+            ..Default::default()
+        }
+    }
     pub fn topic(topic: String) -> Self {
         SubJobDetails {
             topic: Some(topic),
@@ -130,6 +160,40 @@ impl SubJobRepository {
         Ok(sub_job)
     }
 
+    /// Create the sub job unless the job already has one of the type whose details contain the
+    /// key, so a sub job processed again doesn't start its follow up twice
+    pub async fn create_sub_job_once(
+        &self,
+        sub_job_id: Uuid,
+        job_id: Uuid,
+        status: SubJobStatus,
+        job_type: SubJobType,
+        details: SubJobDetails,
+        key: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO sub_jobs (id, job_id, status, type, details)
+            SELECT $1, $2, $3, $4, $5
+            WHERE NOT EXISTS (
+                SELECT 1
+                FROM sub_jobs
+                WHERE job_id = $2 AND type = $4 AND details @> $6
+            )
+            "#,
+            sub_job_id,
+            job_id,
+            status as SubJobStatus,
+            job_type as SubJobType,
+            serde_json::to_value(details).unwrap(),
+            key,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn update_sub_job_status(
         &self,
         sub_job_id: &Uuid,
@@ -214,19 +278,15 @@ impl SubJobRepository {
         Ok(())
     }
 
-    pub async fn count_pending_sub_jobs(
-        &self,
-        sub_job_type: SubJobType,
-        job_id: &Uuid,
-    ) -> Result<i64, sqlx::Error> {
+    /// Count the unfinished sub jobs of the job measuring anything, the scaling sub job aside
+    pub async fn count_pending_sub_jobs(&self, job_id: &Uuid) -> Result<i64, sqlx::Error> {
         let count = sqlx::query!(
             r#"
             SELECT COUNT(*) as count
             FROM sub_jobs
-            WHERE job_id = $1 AND type = $2 AND status IN ('Created', 'Pending', 'Processing')
+            WHERE job_id = $1 AND type <> 'Scaling' AND status IN ('Created', 'Pending', 'Processing')
             "#,
             job_id,
-            sub_job_type as SubJobType,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -234,6 +294,68 @@ impl SubJobRepository {
         Ok(count.count.unwrap())
     }
 
+    pub async fn update_sub_job_aggregate_speed(
+        &self,
+        sub_job_id: &Uuid,
+        aggregate_speed: f64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sub_jobs
+            SET details = details || jsonb_build_object('aggregate_speed', $2::float8)
+            WHERE id = $1
+            "#,
+            sub_job_id,
+            aggregate_speed,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Store the aggregates of the interval logs of a completed download subjob
+    pub async fn update_sub_job_download_aggregates(
+        &self,
+        sub_job_id: &Uuid,
+        download_aggregates: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sub_jobs
+            SET details = details || jsonb_build_object('download_aggregates', $2::jsonb)
+            WHERE id = $1
+            "#,
+            sub_job_id,
+            download_aggregates,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Store the source index of each worker of a multi-URL subjob by worker name
+    pub async fn update_sub_job_assignments(
+        &self,
+        sub_job_id: &Uuid,
+        assignments: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sub_jobs
+            SET details = details || jsonb_build_object('assignments', $2::jsonb)
+            WHERE id = $1
+            "#,
+            sub_job_id,
+            assignments,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_first_unfinished_sub_job(&self) -> Result<SubJobWithJob, sqlx::Error> {
         let sub_job = sqlx::query_as!(
             SubJobWithJob,
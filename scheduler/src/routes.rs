@@ -10,7 +10,7 @@ use axum::{
 use common::api_response::*;
 
 use crate::{
-    api::{healthcheck, jobs, services},
+    api::{credentials, healthcheck, jobs, services},
     config::CONFIG,
     state::AppState,
 };
@@ -34,7 +34,11 @@ pub fn create_routes() -> Router<Arc<AppState>> {
         .route("/jobs", post(jobs::create_job::handle_create_job))
         .route("/jobs", get(jobs::get_jobs::handle_get_jobs))
         .route("/jobs/:job_id", get(jobs::get_job::handle_get_job))
-        .route("/jobs/:job_id", delete(jobs::cancel_job::handle_cancel_job));
+        .route("/jobs/:job_id", delete(jobs::cancel_job::handle_cancel_job))
+        .route(
+            "/jobs/:job_id/rerun",
+            post(jobs::rerun_job::handle_rerun_job),
+        );
 
     let auth_routes = Router::new()
         .route(
@@ -69,6 +73,18 @@ pub fn create_routes() -> Router<Arc<AppState>> {
             "/services/:service_id/scale/down",
             post(services::services_scale_down::handle_services_scale_down),
         )
+        .route(
+            "/credentials",
+            get(credentials::get_credentials::handle_get_credentials),
+        )
+        .route(
+            "/credentials",
+            post(credentials::create_credential::handle_create_credential),
+        )
+        .route(
+            "/credentials/:credential_id",
+            delete(credentials::delete_credential::handle_delete_credential),
+        )
         .layer(middleware::from_fn(auth));
 
     routes.merge(auth_routes)
@@ -0,0 +1,311 @@
+use std::fmt;
+
+use sha2::{Digest, Sha256, Sha512};
+
+// Upper bound of a single section, blocks are limited to a few MB by the gateways
+const MAX_SECTION_SIZE: u64 = 8 * 1024 * 1024;
+
+// Multihash codes
+const IDENTITY: u64 = 0x00;
+const SHA2_256: u64 = 0x12;
+const SHA2_512: u64 = 0x13;
+
+/// Failure of reading a CAR stream
+#[derive(Debug)]
+pub enum CarError {
+    /// The stream is not a valid CARv1
+    Parse(String),
+    /// The data of the block doesn't hash to the digest of its CID
+    BlockHashMismatch { block: usize },
+}
+
+impl fmt::Display for CarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CarError::Parse(msg) => write!(f, "CarParseError: {msg}"),
+            CarError::BlockHashMismatch { block } => {
+                write!(f, "BlockHashMismatch: block {block} does not match its CID")
+            }
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, CarError>;
+
+/// Block of the CAR with the result of the hash check
+pub struct Block {
+    pub data_len: usize,
+    /// False when the hash function of the CID is not supported
+    pub verified: bool,
+}
+
+/// Incremental reader of a CARv1 stream, verifies every block against its CID
+///
+/// The stream is a varint prefixed header followed by varint prefixed sections of a CID and the
+/// block data.
+pub struct CarReader {
+    buffer: Vec<u8>,
+    header_read: bool,
+    blocks: usize,
+}
+
+impl Default for CarReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CarReader {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            header_read: false,
+            blocks: 0,
+        }
+    }
+
+    /// Add received data, returns the blocks completed by it
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<Block>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut blocks = Vec::new();
+        let mut position = 0;
+        while let Some((length, varint_length)) = read_varint(&self.buffer[position..])? {
+            if length > MAX_SECTION_SIZE {
+                return Err(CarError::Parse(format!(
+                    "section of {length} bytes exceeds the limit"
+                )));
+            }
+            let start = position + varint_length;
+            let end = start + length as usize;
+            if self.buffer.len() < end {
+                break;
+            }
+
+            if self.header_read {
+                blocks.push(self.verify_block(&self.buffer[start..end])?);
+                self.blocks += 1;
+            } else {
+                self.header_read = true;
+            }
+            position = end;
+        }
+        self.buffer.drain(..position);
+
+        Ok(blocks)
+    }
+
+    /// Whether the stream ended on a section boundary
+    pub fn is_complete(&self) -> bool {
+        self.header_read && self.buffer.is_empty()
+    }
+
+    fn verify_block(&self, section: &[u8]) -> Result<Block> {
+        let (cid_length, hash_code, digest) = parse_cid(section)?;
+        let data = &section[cid_length..];
+
+        let verified = match hash_code {
+            IDENTITY => Some(data == digest),
+            SHA2_256 => Some(Sha256::digest(data).as_slice() == digest),
+            SHA2_512 => Some(Sha512::digest(data).as_slice() == digest),
+            _ => None,
+        };
+
+        if verified == Some(false) {
+            return Err(CarError::BlockHashMismatch { block: self.blocks });
+        }
+
+        Ok(Block {
+            data_len: data.len(),
+            verified: verified.is_some(),
+        })
+    }
+}
+
+/// Parse the CID at the start of the section, returns its length, hash code and digest
+fn parse_cid(section: &[u8]) -> Result<(usize, u64, &[u8])> {
+    // CIDv0 is a bare sha2-256 multihash
+    if section.len() >= 34 && section[0] == 0x12 && section[1] == 0x20 {
+        return Ok((34, SHA2_256, &section[2..34]));
+    }
+
+    let mut position = 0;
+    let mut next_varint = |section: &[u8]| -> Result<u64> {
+        let Some((value, length)) = read_varint(&section[position..])? else {
+            return Err(CarError::Parse("truncated CID".to_string()));
+        };
+        position += length;
+        Ok(value)
+    };
+
+    let version = next_varint(section)?;
+    if version != 1 {
+        return Err(CarError::Parse(format!(
+            "unsupported CID version {version}"
+        )));
+    }
+    let _codec = next_varint(section)?;
+    let hash_code = next_varint(section)?;
+    let digest_length = next_varint(section)? as usize;
+
+    let digest_end = position + digest_length;
+    if section.len() < digest_end {
+        return Err(CarError::Parse("truncated CID digest".to_string()));
+    }
+
+    Ok((digest_end, hash_code, &section[position..digest_end]))
+}
+
+/// Read an unsigned LEB128 varint, returns the value and its length or `None` when incomplete
+fn read_varint(data: &[u8]) -> Result<Option<(u64, usize)>> {
+    let mut value: u64 = 0;
+    for (index, byte) in data.iter().enumerate() {
+        if index >= 9 {
+            return Err(CarError::Parse("varint is too long".to_string()));
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, index + 1)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // dag-cbor header {"roots": [], "version": 1}, the reader skips it
+    const HEADER: &[u8] = &[
+        0xa2, 0x65, b'r', b'o', b'o', b't', b's', 0x80, 0x67, b'v', b'e', b'r', b's', b'i', b'o',
+        b'n', 0x01,
+    ];
+
+    fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn section(parts: &[&[u8]], out: &mut Vec<u8>) {
+        write_varint(parts.iter().map(|part| part.len() as u64).sum(), out);
+        for part in parts {
+            out.extend_from_slice(part);
+        }
+    }
+
+    /// CIDv1 of a raw block with the given multihash
+    fn cid_v1(hash_code: u64, digest: &[u8]) -> Vec<u8> {
+        let mut cid = vec![0x01, 0x55];
+        write_varint(hash_code, &mut cid);
+        write_varint(digest.len() as u64, &mut cid);
+        cid.extend_from_slice(digest);
+        cid
+    }
+
+    fn car(blocks: &[(Vec<u8>, &[u8])]) -> Vec<u8> {
+        let mut car = Vec::new();
+        section(&[HEADER], &mut car);
+        for (cid, data) in blocks {
+            section(&[cid, data], &mut car);
+        }
+        car
+    }
+
+    #[test]
+    fn reads_blocks_split_across_chunks() {
+        let first: &[u8] = b"hello";
+        let second = vec![7u8; 300];
+        let data = car(&[
+            (cid_v1(SHA2_256, &Sha256::digest(first)), first),
+            (cid_v1(SHA2_512, &Sha512::digest(&second)), &second),
+        ]);
+
+        let mut reader = CarReader::new();
+        let mut blocks = Vec::new();
+        for byte in &data {
+            blocks.extend(reader.push(std::slice::from_ref(byte)).unwrap());
+        }
+
+        assert!(reader.is_complete());
+        let lengths: Vec<_> = blocks.iter().map(|block| block.data_len).collect();
+        assert_eq!(lengths, [5, 300]);
+        assert!(blocks.iter().all(|block| block.verified));
+    }
+
+    #[test]
+    fn verifies_cid_v0_and_identity_blocks() {
+        let data: &[u8] = b"block";
+        let mut cid_v0 = vec![0x12, 0x20];
+        cid_v0.extend_from_slice(&Sha256::digest(data));
+
+        let blocks = CarReader::new()
+            .push(&car(&[(cid_v0, data), (cid_v1(IDENTITY, data), data)]))
+            .unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks.iter().all(|block| block.verified));
+    }
+
+    #[test]
+    fn leaves_unknown_hashes_unverified() {
+        // blake2b-256
+        let blocks = CarReader::new()
+            .push(&car(&[(cid_v1(0xb220, &[0; 32]), b"data")]))
+            .unwrap();
+
+        assert_eq!(blocks.len(), 1);
+        assert!(!blocks[0].verified);
+    }
+
+    #[test]
+    fn rejects_a_block_that_does_not_match_its_cid() {
+        let data: &[u8] = b"block";
+        let result = CarReader::new().push(&car(&[
+            (cid_v1(SHA2_256, &Sha256::digest(data)), data),
+            (cid_v1(SHA2_256, &Sha256::digest(data)), b"other"),
+        ]));
+
+        assert!(matches!(
+            result,
+            Err(CarError::BlockHashMismatch { block: 1 })
+        ));
+    }
+
+    #[test]
+    fn truncated_stream_is_not_complete() {
+        let data: &[u8] = b"block";
+        let car = car(&[(cid_v1(SHA2_256, &Sha256::digest(data)), data)]);
+
+        let mut reader = CarReader::new();
+        assert!(reader.push(&car[..car.len() - 1]).unwrap().is_empty());
+        assert!(!reader.is_complete());
+    }
+
+    #[test]
+    fn rejects_malformed_sections() {
+        let mut oversized = Vec::new();
+        write_varint(MAX_SECTION_SIZE + 1, &mut oversized);
+        assert!(matches!(
+            CarReader::new().push(&oversized),
+            Err(CarError::Parse(_))
+        ));
+
+        let mut cid_v2 = car(&[]);
+        section(&[&[0x02, 0x55, 0x12, 0x00]], &mut cid_v2);
+        assert!(matches!(
+            CarReader::new().push(&cid_v2),
+            Err(CarError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn reads_multi_byte_varints() {
+        assert_eq!(read_varint(&[0xac, 0x02]).unwrap(), Some((300, 2)));
+        assert_eq!(read_varint(&[0xac]).unwrap(), None);
+        assert!(read_varint(&[0xff; 10]).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use sha2::{Digest, Sha256};
+
+/// Unpadded size of a Fr32 chunk, padded to `PADDED_CHUNK_SIZE` bytes
+const UNPADDED_CHUNK_SIZE: usize = 127;
+/// Four 254 bit field elements stored in 32 bytes each
+const PADDED_CHUNK_SIZE: usize = 128;
+const NODE_SIZE: usize = 32;
+// Tree height supporting pieces up to 2^64 bytes
+const MAX_LEVELS: usize = 64;
+
+/// CIDv1, fil-commitment-unsealed codec, sha2-256-trunc254-padded multihash of 32 bytes
+const PIECE_CID_PREFIX: [u8; 7] = [0x01, 0x81, 0xe2, 0x03, 0x92, 0x20, 0x20];
+
+type Node = [u8; NODE_SIZE];
+
+/// Streaming calculation of the Filecoin piece commitment (CommP) of the unpadded piece data
+///
+/// The data is Fr32 padded into 32 byte leaves, zero padded to a power of two and hashed into a
+/// binary merkle tree with SHA-256 truncated to 254 bits.
+pub struct CommP {
+    buffer: Vec<u8>,
+    // Left subtree of each level waiting for its right sibling
+    levels: Vec<Option<Node>>,
+    leaves: u64,
+}
+
+impl Default for CommP {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommP {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::with_capacity(UNPADDED_CHUNK_SIZE),
+            levels: vec![None; MAX_LEVELS + 1],
+            leaves: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = (UNPADDED_CHUNK_SIZE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.buffer.len() == UNPADDED_CHUNK_SIZE {
+                self.process_chunk();
+            }
+        }
+    }
+
+    /// Piece CID of the data, `None` for empty data
+    pub fn finish(mut self) -> Option<String> {
+        if !self.buffer.is_empty() {
+            self.buffer.resize(UNPADDED_CHUNK_SIZE, 0);
+            self.process_chunk();
+        }
+        if self.leaves == 0 {
+            return None;
+        }
+
+        // The missing leaves up to the next power of two are zeros, fold them in level by level
+        let height = self.leaves.next_power_of_two().trailing_zeros() as usize;
+        let mut zero: Node = [0; NODE_SIZE];
+        let mut carry: Option<Node> = None;
+        for level in 0..height {
+            carry = match (self.levels[level], carry) {
+                (Some(left), right) => Some(hash_nodes(&left, &right.unwrap_or(zero))),
+                (None, Some(left)) => Some(hash_nodes(&left, &zero)),
+                (None, None) => None,
+            };
+            zero = hash_nodes(&zero, &zero);
+        }
+        let root = carry.or(self.levels[height])?;
+
+        let mut cid = PIECE_CID_PREFIX.to_vec();
+        cid.extend_from_slice(&root);
+
+        // Multibase prefix of lowercase base32
+        Some(format!("b{}", base32_lower(&cid)))
+    }
+
+    fn process_chunk(&mut self) {
+        let padded = fr32_pad(&self.buffer);
+        self.buffer.clear();
+
+        for leaf in padded.chunks_exact(NODE_SIZE) {
+            self.push_leaf(leaf.try_into().unwrap());
+        }
+    }
+
+    fn push_leaf(&mut self, leaf: Node) {
+        let mut node = leaf;
+        let mut level = 0;
+        while let Some(left) = self.levels[level].take() {
+            node = hash_nodes(&left, &node);
+            level += 1;
+        }
+        self.levels[level] = Some(node);
+        self.leaves += 1;
+    }
+}
+
+/// Spread 127 bytes into four 254 bit field elements, leaving the top two bits of each zero
+fn fr32_pad(input: &[u8]) -> [u8; PADDED_CHUNK_SIZE] {
+    let mut out = [0u8; PADDED_CHUNK_SIZE];
+
+    out[..31].copy_from_slice(&input[..31]);
+    let mut t = input[31] >> 6;
+    out[31] = input[31] & 0x3f;
+
+    let mut v = 0;
+    for i in 32..64 {
+        v = input[i];
+        out[i] = (v << 2) | t;
+        t = v >> 6;
+    }
+
+    t = v >> 4;
+    out[63] &= 0x3f;
+    for i in 64..96 {
+        v = input[i];
+        out[i] = (v << 4) | t;
+        t = v >> 4;
+    }
+
+    t = v >> 2;
+    out[95] &= 0x3f;
+    for i in 96..127 {
+        v = input[i];
+        out[i] = (v << 6) | t;
+        t = v >> 2;
+    }
+
+    out[127] = t & 0x3f;
+
+    out
+}
+
+/// SHA-256 of both nodes truncated to 254 bits
+fn hash_nodes(left: &Node, right: &Node) -> Node {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+
+    let mut node: Node = hasher.finalize().into();
+    node[NODE_SIZE - 1] &= 0x3f;
+
+    node
+}
+
+/// RFC 4648 base32 in lowercase without padding
+fn base32_lower(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Piece CIDs of zero pieces of 128 and 256 padded bytes, the zero commitments of Filecoin
+    const ZERO_PIECE_CID_128: &str =
+        "baga6ea4seaqdomn3tgwgrh3g532zopskstnbrd2n3sxfqbze7rxt7vqn7veigmy";
+    const ZERO_PIECE_CID_256: &str =
+        "baga6ea4seaqgiktap34inmaex4wbs6cghlq5i2j2yd2bb2zndn5ep7ralzphkdy";
+
+    fn piece_cid(data: &[u8]) -> Option<String> {
+        let mut commp = CommP::new();
+        commp.update(data);
+        commp.finish()
+    }
+
+    #[test]
+    fn zero_piece() {
+        assert_eq!(piece_cid(&[0; 127]).as_deref(), Some(ZERO_PIECE_CID_128));
+        assert_eq!(piece_cid(&[0; 254]).as_deref(), Some(ZERO_PIECE_CID_256));
+    }
+
+    #[test]
+    fn zero_padding_up_to_the_power_of_two() {
+        // Five leaves are padded with zero leaves to eight
+        assert_eq!(piece_cid(&[0; 128]).as_deref(), Some(ZERO_PIECE_CID_256));
+    }
+
+    #[test]
+    fn chunks_split_across_updates() {
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+
+        let mut commp = CommP::new();
+        for chunk in data.chunks(100) {
+            commp.update(chunk);
+        }
+
+        assert_eq!(
+            commp.finish().as_deref(),
+            Some("baga6ea4seaqjqyxp26syt72jqcbsqn7ibmae7lck2yqoayiawi7ezozwi4xmgla")
+        );
+    }
+
+    #[test]
+    fn empty_data() {
+        assert_eq!(piece_cid(&[]), None);
+    }
+
+    #[test]
+    fn fr32_padding_leaves_the_top_bits_zero() {
+        let padded = fr32_pad(&[0xff; UNPADDED_CHUNK_SIZE]);
+
+        for element in padded.chunks_exact(NODE_SIZE) {
+            assert!(element[..NODE_SIZE - 1].iter().all(|byte| *byte == 0xff));
+            assert_eq!(element[NODE_SIZE - 1], 0x3f);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Utc};
+use color_eyre::{
+    eyre::{bail, ContextCompat},
+    Result,
+};
+use rabbitmq::ConnectionTiming;
+use reqwest::header::{HeaderMap, ACCEPT, USER_AGENT};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+    time::{timeout, Instant},
+};
+use tokio_native_tls::{native_tls, TlsConnector};
+use tracing::{debug, info};
+use url::Url;
+
+/// Elapsed milliseconds since the given instant
+fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Send a single byte range request over the stream, returns request sent and time to first byte durations
+///
+/// The headers of the job are sent as well, so authenticated targets answer the same as to the
+/// download.
+async fn send_request<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    url: &Url,
+    range_start: i64,
+    headers: &HeaderMap,
+) -> Result<(f64, f64)> {
+    let host = url.host_str().context("Failed to extract host from URL")?;
+    let host_header = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    let path = &url[url::Position::BeforePath..url::Position::AfterQuery];
+
+    let mut request = format!("GET {path} HTTP/1.1\r\nHost: {host_header}\r\n").into_bytes();
+    if !headers.contains_key(USER_AGENT) {
+        request.extend_from_slice(b"User-Agent: curl/7.68.0\r\n");
+    }
+    if !headers.contains_key(ACCEPT) {
+        request.extend_from_slice(b"Accept: */*\r\n");
+    }
+    for (name, value) in headers {
+        request.extend_from_slice(name.as_str().as_bytes());
+        request.extend_from_slice(b": ");
+        request.extend_from_slice(value.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+    request.extend_from_slice(
+        format!("Range: bytes={range_start}-{range_start}\r\nConnection: close\r\n\r\n").as_bytes(),
+    );
+
+    let request_start = Instant::now();
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+    let request_sent_ms = elapsed_ms(request_start);
+
+    let response_start = Instant::now();
+    let mut first_byte = [0u8; 1];
+    if stream.read(&mut first_byte).await? == 0 {
+        bail!("Connection closed before the first byte");
+    }
+    let ttfb_ms = elapsed_ms(response_start);
+
+    Ok((request_sent_ms, ttfb_ms))
+}
+
+/// Measure each phase of a connection to the URL with a separate probe connection
+async fn measure(url: &Url, range_start: i64, headers: &HeaderMap) -> Result<ConnectionTiming> {
+    let host = url.host_str().context("Failed to extract host from URL")?;
+    let port = url
+        .port_or_known_default()
+        .context("Failed to extract port from URL")?;
+
+    let total_start = Instant::now();
+
+    let dns_start = Instant::now();
+    let address = lookup_host(format!("{host}:{port}"))
+        .await?
+        .next()
+        .context("Failed to resolve host")?;
+    let dns_ms = elapsed_ms(dns_start);
+
+    let connect_start = Instant::now();
+    let tcp_stream = TcpStream::connect(address).await?;
+    let connect_ms = elapsed_ms(connect_start);
+
+    let (tls_ms, request_sent_ms, ttfb_ms) = if url.scheme() == "https" {
+        let connector = TlsConnector::from(native_tls::TlsConnector::new()?);
+
+        let tls_start = Instant::now();
+        let mut tls_stream = connector.connect(host, tcp_stream).await?;
+        let tls_ms = elapsed_ms(tls_start);
+
+        let (request_sent_ms, ttfb_ms) =
+            send_request(&mut tls_stream, url, range_start, headers).await?;
+        (Some(tls_ms), request_sent_ms, ttfb_ms)
+    } else {
+        let mut tcp_stream = tcp_stream;
+        let (request_sent_ms, ttfb_ms) =
+            send_request(&mut tcp_stream, url, range_start, headers).await?;
+        (None, request_sent_ms, ttfb_ms)
+    };
+
+    Ok(ConnectionTiming {
+        ip_address: address.ip().to_string(),
+        dns_ms,
+        connect_ms,
+        tls_ms,
+        request_sent_ms,
+        ttfb_ms,
+        total_ms: elapsed_ms(total_start),
+    })
+}
+
+/// Break down the connection setup to the URL into DNS, TCP, TLS, request and first byte phases
+pub async fn process(
+    url: &str,
+    range_start: i64,
+    headers: &HeaderMap,
+    deadline: DateTime<Utc>,
+) -> Result<ConnectionTiming> {
+    info!("Measuring connection timing");
+
+    let url = Url::parse(url)?;
+    let time_left = (deadline - Utc::now()).to_std()?;
+
+    let connection_timing = timeout(time_left, measure(&url, range_start, headers)).await??;
+
+    debug!("Connection timing: {:?}", connection_timing);
+
+    Ok(connection_timing)
+}
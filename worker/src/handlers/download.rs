@@ -1,32 +1,82 @@
+use std::collections::BTreeMap;
+
 use bytes::Bytes;
 use chrono::{DateTime, Duration, Utc};
 use color_eyre::{eyre::bail, Result};
-use rabbitmq::{AccumulatingBytes, DownloadError, DownloadResult, IntervalBytes, JobMessage};
+use futures::future::try_join_all;
+use rabbitmq::{
+    AccumulatingBytes, ConnectionPolicy, DownloadError, DownloadResult, ErrorKind, IntervalBytes,
+    JobMessage, RangeCheck, TcpInfoSample,
+};
 use reqwest::{
-    header::{ACCEPT, RANGE, USER_AGENT},
-    Client, Response,
+    header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_RANGE, RANGE, USER_AGENT},
+    Client, RequestBuilder, Response, StatusCode,
 };
+use sha2::{Digest, Sha256};
 use tokio::time::{sleep, timeout};
 use tracing::{debug, error, info};
+use url::Url;
 use uuid::Uuid;
 
-// Download deadline, job will succeed but won't work/download more than this duration
-const MAX_DOWNLOAD_DURATION: Duration = Duration::seconds(60);
+use super::{
+    connection_timing,
+    errors::reqwest_error_kind,
+    protocol::{build_client, protocol_info},
+};
+use crate::{commp::CommP, tcp_info::TcpInfoSocket};
 
-/// Prepare the HTTP request
-fn prepare_request(url: &str, range_start: i64, range_end: i64) -> reqwest::RequestBuilder {
+/// Prepare the HTTP request, the custom headers replace the default ones
+pub(super) fn prepare_request(
+    client: &Client,
+    url: &str,
+    range_start: i64,
+    range_end: i64,
+    headers: HeaderMap,
+) -> RequestBuilder {
     const USER_AGENT_STR: &str = "curl/7.68.0";
     const ACCEPT_TYPE: &str = "*/*";
 
-    Client::new()
+    client
         .get(url)
         .header(RANGE, format!("bytes={range_start}-{range_end}"))
         .header(USER_AGENT, USER_AGENT_STR)
         .header(ACCEPT, ACCEPT_TYPE)
+        .headers(headers)
+}
+
+/// Convert the custom headers of the job, invalid ones are rejected by the scheduler
+///
+/// Requests to an S3 source get the SigV4 headers of the method, signed when they are built.
+pub(super) fn header_map(payload: &JobMessage, method: &str) -> HeaderMap {
+    let signed_headers = match (&payload.s3_signing, Url::parse(&payload.url)) {
+        (Some(s3_signing), Ok(url)) => s3_signing.signed_headers(method, &url),
+        _ => Vec::new(),
+    };
+
+    payload
+        .headers
+        .0
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .chain(
+            signed_headers
+                .iter()
+                .map(|(name, value)| (*name, value.as_str())),
+        )
+        .filter_map(|(name, value)| {
+            Some((
+                HeaderName::try_from(name).ok()?,
+                HeaderValue::try_from(value).ok()?,
+            ))
+        })
+        .collect()
 }
 
 /// Calculates the next interval based on the current time and the specified interval in milliseconds.
-fn calculate_next_interval(current: DateTime<Utc>, interval_milis: i64) -> DateTime<Utc> {
+pub(super) fn calculate_next_interval(
+    current: DateTime<Utc>,
+    interval_milis: i64,
+) -> DateTime<Utc> {
     let millis = current.timestamp_millis() % interval_milis;
     let remaining_millis = interval_milis - millis;
 
@@ -34,7 +84,7 @@ fn calculate_next_interval(current: DateTime<Utc>, interval_milis: i64) -> DateT
 }
 
 /// Sleep until the start time of the job
-async fn wait_for_start_time(payload: &JobMessage) -> Result<()> {
+pub(super) async fn wait_for_start_time(payload: &JobMessage) -> Result<()> {
     let now = Utc::now();
 
     if payload.download_start_time < now {
@@ -59,69 +109,312 @@ async fn wait_for_start_time(payload: &JobMessage) -> Result<()> {
     Ok(())
 }
 
-async fn download_chunk(response: &mut Response) -> Result<Option<Bytes>, DownloadError> {
-    match timeout(MAX_DOWNLOAD_DURATION.to_std().unwrap(), response.chunk()).await {
+async fn download_chunk(
+    response: &mut Response,
+    max_duration: Duration,
+) -> Result<Option<Bytes>, DownloadError> {
+    match timeout(max_duration.to_std().unwrap_or_default(), response.chunk()).await {
         Ok(Ok(chunk)) => Ok(chunk),
-        Ok(Err(e)) => Err(DownloadError {
-            error: format!("ChunkError: {e}"),
-        }),
+        Ok(Err(e)) => Err(DownloadError::new(
+            reqwest_error_kind(&e),
+            format!("ChunkError: {e}"),
+        )),
         _ => Ok(None),
     }
 }
 
+/// Get the piece CID from the last segment of the URL path, e.g. `/piece/baga6ea4sea...`
+fn piece_cid_from_url(url: &str) -> Result<String, DownloadError> {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| Some(url.path_segments()?.next_back()?.to_string()))
+        .filter(|segment| segment.starts_with("baga"))
+        .ok_or(DownloadError::new(
+            ErrorKind::PieceCid,
+            "PieceCidError: URL does not contain a piece CID",
+        ))
+}
+
+/// Check that the server responded with exactly the requested range, returns the Content-Range
+pub(super) fn check_range_response(
+    response: &Response,
+    range_start: i64,
+    range_end: i64,
+) -> Result<String, DownloadError> {
+    // A server ignoring the range responds with 200 and the whole file
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(DownloadError::new(
+            ErrorKind::RangeNotSupported,
+            format!(
+                "RangeNotSupported: expected 206 Partial Content, got {}",
+                response.status()
+            ),
+        ));
+    }
+
+    let content_range = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(DownloadError::new(
+            ErrorKind::ContentRangeMismatch,
+            "ContentRangeMismatch: missing Content-Range header",
+        ))?;
+
+    // Content-Range: bytes <start>-<end>/<size>
+    let range = content_range
+        .strip_prefix("bytes ")
+        .and_then(|range| range.split('/').next())
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, end)| Some((start.parse::<i64>().ok()?, end.parse::<i64>().ok()?)));
+
+    if range != Some((range_start, range_end)) {
+        return Err(DownloadError::new(
+            ErrorKind::ContentRangeMismatch,
+            format!(
+                "ContentRangeMismatch: expected bytes {range_start}-{range_end}, got {content_range}"
+            ),
+        ));
+    }
+
+    Ok(content_range.to_string())
+}
+
+/// Split the inclusive byte range into `streams` consecutive ranges
+fn split_range(range_start: i64, range_end: i64, streams: i64) -> Vec<(i64, i64)> {
+    let total = range_end - range_start + 1;
+    let streams = streams.clamp(1, total.max(1));
+    let chunk_size = total / streams;
+
+    (0..streams)
+        .map(|index| {
+            let start = range_start + index * chunk_size;
+            let end = if index == streams - 1 {
+                range_end
+            } else {
+                start + chunk_size - 1
+            };
+            (start, end)
+        })
+        .collect()
+}
+
+/// Calculates the transfer speed in Mbps
+pub(super) fn calculate_speed(total_bytes: usize, elapsed_secs: f64) -> f64 {
+    // Convert to bits and then to kilo and mega bits per second
+    (total_bytes as f64 * 8.0) / (elapsed_secs * 1024.0 * 1024.0)
+}
+
 /// Benchmark the download speed of the given URL
 #[tracing::instrument(skip(payload))]
 pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<DownloadResult, DownloadError> {
     info!("Processing Download job");
 
-    let request = prepare_request(&payload.url, payload.start_range, payload.end_range);
+    // Download deadline, job will succeed but won't work/download more than this duration
+    let max_duration = Duration::seconds(payload.max_duration_secs);
+
+    // The piece commitment is calculated over the data in order, so the piece is downloaded as one stream
+    let (expected_piece_cid, streams_per_worker) = if payload.verify_piece_cid {
+        (Some(piece_cid_from_url(&payload.url)?), 1)
+    } else {
+        (None, payload.streams_per_worker)
+    };
+
+    let http_protocol = payload.http_protocol.unwrap_or_default();
+    let new_client = || {
+        build_client(http_protocol)
+            .map_err(|e| DownloadError::new(ErrorKind::Internal, format!("ClientError: {e}")))
+    };
+    // Streams get a connection each unless the connections are reused
+    let shared_client = match payload.connection_policy {
+        Some(ConnectionPolicy::Reuse) => Some(new_client()?),
+        Some(ConnectionPolicy::Fresh) | None => None,
+    };
+
+    let requests: Vec<(RequestBuilder, (i64, i64))> =
+        split_range(payload.start_range, payload.end_range, streams_per_worker)
+            .into_iter()
+            .map(|(range_start, range_end)| {
+                let client = match &shared_client {
+                    Some(client) => client.clone(),
+                    None => new_client()?,
+                };
+                Ok((
+                    prepare_request(
+                        &client,
+                        &payload.url,
+                        range_start,
+                        range_end,
+                        header_map(&payload, "GET"),
+                    ),
+                    (range_start, range_end),
+                ))
+            })
+            .collect::<Result<_, DownloadError>>()?;
+
+    // Probe the connection phases on a separate connection before the synchronized download starts
+    let connection_timing = connection_timing::process(
+        &payload.url,
+        payload.start_range,
+        &header_map(&payload, "GET"),
+        payload.download_start_time - Duration::seconds(2),
+    )
+    .await
+    .inspect_err(|e| error!("Failed to measure connection timing: {e}"))
+    .ok();
 
     let job_start_time = Utc::now();
+
+    // Delay the download execution to sync the time on every worker
+    wait_for_start_time(&payload)
+        .await
+        .map_err(|e| DownloadError::new(ErrorKind::TimeSync, format!("TimeSyncError: {e}")))?;
+
+    if requests.len() == 1 {
+        let (request, range) = requests.into_iter().next().unwrap();
+        let mut result = download_stream(
+            request,
+            range,
+            job_start_time,
+            payload.log_interval_ms,
+            max_duration,
+            payload.hash_content,
+            expected_piece_cid,
+        )
+        .await?;
+        result.connection_timing = connection_timing;
+
+        return Ok(result);
+    }
+
+    info!("Downloading with {} parallel streams", requests.len());
+
+    // Each stream runs on its own connection, so all of them start at the same time
+    let mut handles: Vec<_> = requests
+        .into_iter()
+        .map(|(request, range)| {
+            tokio::spawn(download_stream(
+                request,
+                range,
+                job_start_time,
+                payload.log_interval_ms,
+                max_duration,
+                payload.hash_content,
+                None,
+            ))
+        })
+        .collect();
+
+    // The first failed stream fails the download, the other streams are stopped with it
+    let joined = try_join_all(
+        handles
+            .iter_mut()
+            .enumerate()
+            .map(|(index, handle)| async move {
+                handle
+                    .await
+                    .map_err(|e| {
+                        DownloadError::new(ErrorKind::Internal, format!("StreamJoinError: {e}"))
+                    })?
+                    .map_err(|e| DownloadError::new(e.kind, format!("Stream {index}: {}", e.error)))
+            }),
+    )
+    .await;
+    let streams = match joined {
+        Ok(streams) => streams,
+        Err(e) => {
+            for handle in &handles {
+                handle.abort();
+            }
+            return Err(e);
+        }
+    };
+
+    let mut result = combine_streams(streams, payload.log_interval_ms);
+    result.connection_timing = connection_timing;
+
+    info!(
+        "Downloaded {} bytes with {} streams in {:.2} seconds ({:.2} Mbps, {:.2} MBps)",
+        result.total_bytes,
+        result.streams.len(),
+        result.elapsed_secs,
+        result.download_speed,
+        result.download_speed / 8.0
+    );
+
+    Ok(result)
+}
+
+/// Download a single range and log the downloaded bytes on every interval
+async fn download_stream(
+    request: RequestBuilder,
+    (range_start, range_end): (i64, i64),
+    job_start_time: DateTime<Utc>,
+    log_interval_ms: i64,
+    max_duration: Duration,
+    hash_content: bool,
+    expected_piece_cid: Option<String>,
+) -> Result<DownloadResult, DownloadError> {
     let mut bytes: usize = 0;
     let mut total_bytes: usize = 0;
     let mut second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)> =
         Vec::new();
+    let mut tcp_info_samples: Vec<(DateTime<Utc>, TcpInfoSample)> = Vec::new();
 
-    // Delay the download execution to sync the time on every worker
-    wait_for_start_time(&payload)
+    let mut response = request
+        .send()
         .await
-        .map_err(|e| DownloadError {
-            error: format!("TimeSyncError: {e}"),
-        })?;
-
-    let mut response = request.send().await.map_err(|e| DownloadError {
-        error: format!("RequestError: {e}"),
-    })?;
+        .map_err(|e| DownloadError::new(reqwest_error_kind(&e), format!("RequestError: {e}")))?;
 
     if !response.status().is_success() {
-        return Err(DownloadError {
-            error: format!("RequestFailed: {}", response.status()),
-        });
+        return Err(DownloadError::new(
+            ErrorKind::HttpStatus {
+                code: response.status().as_u16(),
+            },
+            format!("RequestFailed: {}", response.status()),
+        ));
     }
 
+    let content_range = check_range_response(&response, range_start, range_end)?;
+    let protocol = protocol_info(&response);
+    let expected_bytes = (range_end - range_start + 1) as usize;
+    let mut hasher = hash_content.then(Sha256::new);
+    let mut commp = expected_piece_cid.as_ref().map(|_| CommP::new());
+
     let time_to_first_byte_ms = (Utc::now() - job_start_time).num_milliseconds() as f64;
     debug!("Time to first byte: {} ms", time_to_first_byte_ms);
 
+    let tcp_info_socket = TcpInfoSocket::from_response(&response);
+    if tcp_info_socket.is_none() {
+        debug!("TCP_INFO socket not found, samples will not be collected");
+    }
+
     // It seems that time to first byte can be quite long, so we need to adjust the start time for better download speed calculation
     let download_start_time = Utc::now();
-    let mut next_log_time = calculate_next_interval(download_start_time, payload.log_interval_ms);
+    let mut next_log_time = calculate_next_interval(download_start_time, log_interval_ms);
 
     debug!(
         "job_start_time: {}, download_start_time: {}, next_log_time: {}, log_interval_ms: {}",
-        job_start_time, download_start_time, next_log_time, payload.log_interval_ms
+        job_start_time, download_start_time, next_log_time, log_interval_ms
     );
 
-    while let Some(chunk) = download_chunk(&mut response).await? {
+    while let Some(chunk) = download_chunk(&mut response, max_duration).await? {
         let chunk_size = chunk.len();
         bytes += chunk_size;
         total_bytes += chunk_size;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+        if let Some(commp) = commp.as_mut() {
+            commp.update(&chunk);
+        }
 
         let current_time = Utc::now();
         let elapsed_time = current_time - download_start_time;
-        if elapsed_time >= MAX_DOWNLOAD_DURATION {
+        if elapsed_time >= max_duration {
             info!(
                 "Reached maximum download duration of {:?}, stopping download",
-                MAX_DOWNLOAD_DURATION
+                max_duration
             );
             break;
         }
@@ -133,6 +426,9 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<DownloadResult
                 IntervalBytes(bytes),
                 AccumulatingBytes(total_bytes),
             ));
+            if let Some(sample) = tcp_info_socket.as_ref().and_then(|s| s.sample()) {
+                tcp_info_samples.push((current_time, sample));
+            }
             debug!(
                 "Time: {:?}, Bytes downloaded: {}",
                 current_time, total_bytes
@@ -141,7 +437,7 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<DownloadResult
             // Reset the interval byte counter
             bytes = 0;
             // Increment next log time to the next even second
-            next_log_time = calculate_next_interval(current_time, payload.log_interval_ms);
+            next_log_time = calculate_next_interval(current_time, log_interval_ms);
             debug!(
                 "Duration from current time {:?}",
                 (next_log_time - current_time).num_milliseconds()
@@ -150,15 +446,35 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<DownloadResult
     }
 
     if total_bytes == 0 {
-        return Err(DownloadError {
-            error: "Downloaded 0 bytes".to_string(),
-        });
+        return Err(DownloadError::new(
+            ErrorKind::ZeroBytes,
+            "Downloaded 0 bytes",
+        ));
     }
 
     let end_time = Utc::now();
+
+    // The download may stop at the deadline, but the body must not be longer nor end early
+    let reached_deadline = end_time - download_start_time >= max_duration;
+    if total_bytes > expected_bytes || (total_bytes < expected_bytes && !reached_deadline) {
+        return Err(DownloadError::new(
+            ErrorKind::ByteCountMismatch,
+            format!("ByteCountMismatch: expected {expected_bytes} bytes, received {total_bytes}"),
+        ));
+    }
+    let complete = total_bytes == expected_bytes;
+
+    // A piece downloaded only partially can't be verified, neither way
+    let piece_cid = commp.filter(|_| complete).and_then(CommP::finish);
+    let piece_cid_verified = expected_piece_cid
+        .filter(|_| complete)
+        .map(|expected| piece_cid.as_ref() == Some(&expected));
+    if piece_cid_verified == Some(false) {
+        error!("Piece CID verification failed, piece_cid: {:?}", piece_cid);
+    }
+
     let elapsed_secs = (end_time - download_start_time).num_milliseconds() as f64 / 1000.0;
-    // Convert to bits and then to kilo and mega bits per second
-    let download_speed = (total_bytes as f64 * 8.0) / (elapsed_secs * 1024.0 * 1024.0);
+    let download_speed = calculate_speed(total_bytes, elapsed_secs);
 
     info!(
         "Downloaded {} bytes in {:.2} seconds ({:.2} Mbps, {:.2} MBps)",
@@ -177,5 +493,156 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<DownloadResult
         end_time,
         time_to_first_byte_ms,
         second_by_second_logs,
+        connection_timing: None,
+        tcp_info_samples,
+        loaded_latency: None,
+        range_check: Some(RangeCheck {
+            status_code: response.status().as_u16(),
+            content_range,
+            expected_bytes,
+            complete,
+            sha256: hasher
+                .filter(|_| complete)
+                .map(|hasher| format!("{:x}", hasher.finalize())),
+        }),
+        piece_cid,
+        piece_cid_verified,
+        streams: vec![],
+        protocol: Some(protocol),
     })
 }
+
+/// Combine the results of parallel streams into a single worker result
+fn combine_streams(streams: Vec<DownloadResult>, log_interval_ms: i64) -> DownloadResult {
+    let total_bytes: usize = streams.iter().map(|s| s.total_bytes).sum();
+    let job_start_time = streams.iter().map(|s| s.job_start_time).min().unwrap();
+    let download_start_time = streams.iter().map(|s| s.download_start_time).min().unwrap();
+    let end_time = streams.iter().map(|s| s.end_time).max().unwrap();
+    let time_to_first_byte_ms = streams
+        .iter()
+        .map(|s| s.time_to_first_byte_ms)
+        .fold(f64::INFINITY, f64::min);
+
+    let elapsed_secs = (end_time - download_start_time).num_milliseconds() as f64 / 1000.0;
+    let download_speed = calculate_speed(total_bytes, elapsed_secs);
+
+    DownloadResult {
+        total_bytes,
+        elapsed_secs,
+        download_speed,
+        job_start_time,
+        download_start_time,
+        end_time,
+        time_to_first_byte_ms,
+        second_by_second_logs: merge_interval_logs(&streams, log_interval_ms),
+        connection_timing: None,
+        tcp_info_samples: vec![],
+        loaded_latency: None,
+        range_check: None,
+        piece_cid: None,
+        piece_cid_verified: None,
+        // Streams use the same protocol, the first one represents them
+        protocol: streams.first().and_then(|s| s.protocol.clone()),
+        streams,
+    }
+}
+
+/// Merge interval logs of all streams by summing the bytes logged within the same interval
+fn merge_interval_logs(
+    streams: &[DownloadResult],
+    log_interval_ms: i64,
+) -> Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)> {
+    let mut intervals: BTreeMap<i64, usize> = BTreeMap::new();
+
+    for stream in streams {
+        for (time, interval_bytes, _) in &stream.second_by_second_logs {
+            // Each log is written right after the interval boundary, group by that boundary
+            let millis = time.timestamp_millis();
+            let interval = millis - millis % log_interval_ms;
+            *intervals.entry(interval).or_default() += interval_bytes.0;
+        }
+    }
+
+    let mut total_bytes: usize = 0;
+    intervals
+        .into_iter()
+        .filter_map(|(interval, bytes)| {
+            total_bytes += bytes;
+            DateTime::from_timestamp_millis(interval)
+                .map(|time| (time, IntervalBytes(bytes), AccumulatingBytes(total_bytes)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(logs: &[(i64, usize)]) -> DownloadResult {
+        let time = DateTime::from_timestamp_millis(0).unwrap();
+        let mut total_bytes = 0;
+        let second_by_second_logs = logs
+            .iter()
+            .map(|&(millis, bytes)| {
+                total_bytes += bytes;
+                (
+                    DateTime::from_timestamp_millis(millis).unwrap(),
+                    IntervalBytes(bytes),
+                    AccumulatingBytes(total_bytes),
+                )
+            })
+            .collect();
+
+        DownloadResult {
+            total_bytes,
+            elapsed_secs: 0.0,
+            download_speed: 0.0,
+            job_start_time: time,
+            download_start_time: time,
+            end_time: time,
+            time_to_first_byte_ms: 0.0,
+            second_by_second_logs,
+            connection_timing: None,
+            tcp_info_samples: Vec::new(),
+            loaded_latency: None,
+            range_check: None,
+            piece_cid: None,
+            piece_cid_verified: None,
+            streams: Vec::new(),
+            protocol: None,
+        }
+    }
+
+    #[test]
+    fn split_range_gives_the_remainder_to_the_last_stream() {
+        assert_eq!(split_range(0, 9, 3), [(0, 2), (3, 5), (6, 9)]);
+        assert_eq!(
+            split_range(100, 199, 4),
+            [(100, 124), (125, 149), (150, 174), (175, 199)]
+        );
+    }
+
+    #[test]
+    fn split_range_uses_at_most_one_stream_per_byte() {
+        assert_eq!(split_range(0, 2, 8), [(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(split_range(5, 5, 4), [(5, 5)]);
+        assert_eq!(split_range(0, 9, 0), [(0, 9)]);
+    }
+
+    #[test]
+    fn merge_interval_logs_sums_the_streams_per_interval() {
+        let streams = [
+            stream(&[(1_003, 10), (2_001, 20), (3_002, 30)]),
+            stream(&[(1_010, 1), (2_015, 2)]),
+        ];
+
+        let merged: Vec<_> = merge_interval_logs(&streams, 1000)
+            .into_iter()
+            .map(|(time, interval, accumulating)| {
+                (time.timestamp_millis(), interval.0, accumulating.0)
+            })
+            .collect();
+
+        assert_eq!(merged, [(1_000, 11, 11), (2_000, 22, 33), (3_000, 30, 63)]);
+    }
+}
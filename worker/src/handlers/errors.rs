@@ -0,0 +1,65 @@
+use std::{error::Error, io};
+
+use rabbitmq::ErrorKind;
+use tokio_native_tls::native_tls;
+
+/// Classify the reqwest error by walking its sources down to the IO or TLS error
+pub(super) fn reqwest_error_kind(e: &reqwest::Error) -> ErrorKind {
+    if let Some(status) = e.status() {
+        return ErrorKind::HttpStatus {
+            code: status.as_u16(),
+        };
+    }
+    if e.is_builder() {
+        return ErrorKind::InvalidUrl;
+    }
+    if e.is_body() || e.is_decode() {
+        return if e.is_timeout() {
+            ErrorKind::BodyTimeout
+        } else {
+            ErrorKind::Body
+        };
+    }
+
+    let mut source = e.source();
+    while let Some(cause) = source {
+        if cause.is::<native_tls::Error>() {
+            return ErrorKind::Tls;
+        }
+        if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+            // Source of an IO error skips the wrapped error itself
+            if io_error
+                .get_ref()
+                .is_some_and(|inner| inner.is::<native_tls::Error>())
+            {
+                return ErrorKind::Tls;
+            }
+            match io_error_kind(io_error) {
+                ErrorKind::Connect => {}
+                kind => return kind,
+            }
+        }
+        // The resolver error of hyper is private, only its message identifies it
+        if cause.to_string().starts_with("dns error") {
+            return ErrorKind::Dns;
+        }
+        source = cause.source();
+    }
+
+    if e.is_timeout() {
+        ErrorKind::ConnectTimeout
+    } else if e.is_connect() {
+        ErrorKind::Connect
+    } else {
+        ErrorKind::Request
+    }
+}
+
+/// Classify the error of a TCP connect
+pub(super) fn io_error_kind(e: &io::Error) -> ErrorKind {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => ErrorKind::ConnectRefused,
+        io::ErrorKind::TimedOut => ErrorKind::ConnectTimeout,
+        _ => ErrorKind::Connect,
+    }
+}
@@ -1,18 +1,35 @@
 use chrono::{Duration, Utc};
 use color_eyre::Result;
-use rabbitmq::{HeadError, HeadResult, JobMessage};
-use reqwest::Client;
+use rabbitmq::{ConnectionPolicy, ErrorKind, HeadError, HeadResult, JobMessage, ProtocolInfo};
 use tokio::time::Instant;
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
+use super::{
+    download::header_map,
+    errors::reqwest_error_kind,
+    latency::calculate_stats,
+    protocol::{build_client, negotiate_tls, protocol_info, with_tls_details},
+};
+
 #[tracing::instrument(skip(payload))]
 pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<HeadResult, HeadError> {
     info!("Processing HEAD job");
 
-    let client = Client::new();
+    let http_protocol = payload.http_protocol.unwrap_or_default();
+    let new_client = || {
+        build_client(http_protocol)
+            .map_err(|e| HeadError::new(ErrorKind::Internal, format!("ClientError: {e}")))
+    };
+    // The requests share a connection unless fresh connections are requested
+    let fresh_connections = payload.connection_policy == Some(ConnectionPolicy::Fresh);
+    let mut client = new_client()?;
+    let mut protocol: Option<ProtocolInfo> = None;
     let num_requests = 10; // Number of times to send the HEAD request
     let mut latencies: Vec<f64> = Vec::with_capacity(num_requests);
+    let mut status_codes: Vec<u16> = Vec::with_capacity(num_requests);
+    let mut probes: usize = 0;
+    let mut last_error: Option<ErrorKind> = None;
 
     // Calculate deadline
     let loop_deadline = payload.download_start_time - Duration::seconds(2);
@@ -25,22 +42,33 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<HeadResult, He
             info!("Loop deadline reached, aborting the loop");
             break;
         }
+        probes += 1;
+
+        if fresh_connections {
+            client = new_client()?;
+        }
 
         let start_time = Instant::now(); // Start timing
 
-        // Send a HEAD request to the URL
-        let response = client
+        // Send a HEAD request to the URL, failed requests are counted as lost
+        let request = client
             .head(&payload.url)
-            .send()
-            .await
-            .map_err(|e| HeadError {
-                error: format!("RequestError: {e}"),
-            })?;
+            .headers(header_map(&payload, "HEAD"));
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                error!("RequestError: {}", e);
+                last_error = Some(reqwest_error_kind(&e));
+                continue;
+            }
+        };
 
         // Measure the elapsed time
         let elapsed = start_time.elapsed();
         let latency_ms = elapsed.as_secs_f64() * 1000.0; // Convert to milliseconds
         latencies.push(latency_ms);
+        status_codes.push(response.status().as_u16());
+        protocol = Some(protocol_info(&response));
 
         // Print the status code to verify the request
         debug!(
@@ -51,9 +79,11 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<HeadResult, He
     }
 
     if latencies.is_empty() {
-        return Err(HeadError {
-            error: "No successful requests".to_string(),
-        });
+        // Every request failed, report the kind of the last failure
+        return Err(HeadError::new(
+            last_error.unwrap_or(ErrorKind::Request),
+            "No successful requests",
+        ));
     }
 
     // Calculate min, max, and average latencies
@@ -66,11 +96,34 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<HeadResult, He
     debug!("Min: {:.2} ms", min_latency);
     debug!("Max: {:.2} ms", max_latency);
 
+    let stats = calculate_stats(latencies, probes);
+    debug!(
+        "Loss: {:.2} %, Jitter: {:.2} ms",
+        stats.loss_percent, stats.jitter
+    );
+
+    // Negotiate TLS on a probe connection with the time left before the download
+    let protocol = match (protocol, (loop_deadline - Utc::now()).to_std()) {
+        (Some(info), Ok(time_left)) => {
+            match negotiate_tls(&payload.url, http_protocol, time_left).await {
+                Ok(tls_details) => Some(with_tls_details(info, &tls_details)),
+                Err(e) => {
+                    error!("Failed to negotiate TLS: {e}");
+                    Some(info)
+                }
+            }
+        }
+        (protocol, _) => protocol,
+    };
+
     info!("Finished processing HEAD job");
 
     Ok(HeadResult {
         min: min_latency,
         max: max_latency,
         avg: avg_latency,
+        stats,
+        status_codes,
+        protocol,
     })
 }
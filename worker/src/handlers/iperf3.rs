@@ -0,0 +1,388 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration as StdDuration,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use rabbitmq::{
+    AccumulatingBytes, ErrorKind, IntervalBytes, Iperf3Error, Iperf3Result, Iperf3Server,
+    JobMessage,
+};
+use rand::{thread_rng, Rng};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::{
+    download::{calculate_next_interval, calculate_speed, wait_for_start_time},
+    errors::io_error_kind,
+};
+
+// States of the iperf3 control connection, sent as a single signed byte
+const TEST_START: i8 = 1;
+const TEST_RUNNING: i8 = 2;
+const TEST_END: i8 = 4;
+const PARAM_EXCHANGE: i8 = 9;
+const CREATE_STREAMS: i8 = 10;
+const SERVER_TERMINATE: i8 = 11;
+const EXCHANGE_RESULTS: i8 = 13;
+const DISPLAY_RESULTS: i8 = 14;
+const IPERF_DONE: i8 = 16;
+const ACCESS_DENIED: i8 = -1;
+const SERVER_ERROR: i8 = -2;
+
+// Cookie identifying the test on every connection, 36 characters and a NUL
+const COOKIE_SIZE: usize = 37;
+const COOKIE_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+// Block size of the server writes, the iperf3 default for TCP
+const BLOCK_SIZE: usize = 128 * 1024;
+const CONNECT_TIMEOUT: StdDuration = StdDuration::from_secs(5);
+// Time given to the server to answer on the control connection
+const CONTROL_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+const CLIENT_VERSION: &str = "3.12";
+
+fn protocol_error(error: impl Into<String>) -> Iperf3Error {
+    Iperf3Error::new(ErrorKind::Iperf3Protocol, error)
+}
+
+fn io_error(context: &str, e: io::Error) -> Iperf3Error {
+    Iperf3Error::new(io_error_kind(&e), format!("{context}: {e}"))
+}
+
+fn generate_cookie() -> [u8; COOKIE_SIZE] {
+    let mut rng = thread_rng();
+    let mut cookie = [0u8; COOKIE_SIZE];
+    for byte in &mut cookie[..COOKIE_SIZE - 1] {
+        *byte = COOKIE_CHARS[rng.gen_range(0..COOKIE_CHARS.len())];
+    }
+    cookie
+}
+
+/// Stream ids assigned by the server, the second stream gets 3 for historical reasons
+fn stream_id(index: usize) -> usize {
+    if index == 0 {
+        1
+    } else {
+        index + 2
+    }
+}
+
+/// Open a connection to the server and identify it with the cookie of the test
+async fn connect(address: &str, cookie: &[u8; COOKIE_SIZE]) -> Result<TcpStream, Iperf3Error> {
+    let socket_address = lookup_host(address)
+        .await
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| {
+            Iperf3Error::new(
+                ErrorKind::Dns,
+                format!("DnsError: failed to resolve {address}"),
+            )
+        })?;
+    let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(socket_address))
+        .await
+        .map_err(|_| {
+            Iperf3Error::new(
+                ErrorKind::ConnectTimeout,
+                format!("ConnectTimeout: {address}"),
+            )
+        })?
+        .map_err(|e| io_error("ConnectError", e))?;
+    stream
+        .write_all(cookie)
+        .await
+        .map_err(|e| io_error("ConnectError", e))?;
+
+    Ok(stream)
+}
+
+async fn read_state(control: &mut TcpStream) -> Result<i8, Iperf3Error> {
+    let state = timeout(CONTROL_TIMEOUT, control.read_i8())
+        .await
+        .map_err(|_| protocol_error("ControlTimeout: server did not answer"))?
+        .map_err(|e| io_error("ControlError", e))?;
+
+    match state {
+        SERVER_ERROR => {
+            // The server error is followed by its iperf3 error number and errno
+            let i_errno = control.read_i32().await.unwrap_or_default();
+            let errno = control.read_i32().await.unwrap_or_default();
+            Err(protocol_error(format!(
+                "ServerError: iperf3 error {i_errno}, errno {errno}"
+            )))
+        }
+        SERVER_TERMINATE => Err(protocol_error("ServerError: server terminated the test")),
+        state => Ok(state),
+    }
+}
+
+async fn expect_state(control: &mut TcpStream, expected: i8) -> Result<(), Iperf3Error> {
+    match read_state(control).await? {
+        state if state == expected => Ok(()),
+        state => Err(protocol_error(format!(
+            "UnexpectedState: expected {expected}, got {state}"
+        ))),
+    }
+}
+
+async fn write_state(control: &mut TcpStream, state: i8) -> Result<(), Iperf3Error> {
+    control
+        .write_i8(state)
+        .await
+        .map_err(|e| io_error("ControlError", e))
+}
+
+/// JSON messages are prefixed with their length as a 32-bit big-endian integer
+async fn write_json(control: &mut TcpStream, value: &Value) -> Result<(), Iperf3Error> {
+    let data = value.to_string();
+    control
+        .write_u32(data.len() as u32)
+        .await
+        .map_err(|e| io_error("ControlError", e))?;
+    control
+        .write_all(data.as_bytes())
+        .await
+        .map_err(|e| io_error("ControlError", e))
+}
+
+async fn read_json(control: &mut TcpStream) -> Result<Value, Iperf3Error> {
+    let read = async {
+        let len = control.read_u32().await?;
+        let mut data = vec![0u8; len as usize];
+        control.read_exact(&mut data).await?;
+        Ok::<_, io::Error>(data)
+    };
+    let data = timeout(CONTROL_TIMEOUT, read)
+        .await
+        .map_err(|_| protocol_error("ControlTimeout: server did not send the results"))?
+        .map_err(|e| io_error("ControlError", e))?;
+
+    serde_json::from_slice(&data).map_err(|e| protocol_error(format!("InvalidResults: {e}")))
+}
+
+/// Take the first server instance that isn't running another test
+async fn find_free_instance(
+    server: &Iperf3Server,
+    cookie: &[u8; COOKIE_SIZE],
+) -> Result<(String, TcpStream), Iperf3Error> {
+    for port in (server.port..).take(server.port_count.max(1) as usize) {
+        let address = format!("{}:{port}", server.host);
+        let mut control = connect(&address, cookie).await?;
+
+        match read_state(&mut control).await? {
+            PARAM_EXCHANGE => return Ok((address, control)),
+            ACCESS_DENIED => debug!("iperf3 server {} is busy", address),
+            state => {
+                return Err(protocol_error(format!(
+                    "UnexpectedState: expected {PARAM_EXCHANGE}, got {state}"
+                )))
+            }
+        }
+    }
+
+    Err(Iperf3Error::new(
+        ErrorKind::ServerBusy,
+        format!(
+            "ServerBusy: all {} instances of {} are running other tests",
+            server.port_count, server.host
+        ),
+    ))
+}
+
+/// Count the bytes received on the data stream until it's closed or aborted
+fn spawn_reader(mut stream: TcpStream, received: Arc<AtomicUsize>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut buffer = vec![0u8; BLOCK_SIZE];
+        while let Ok(read @ 1..) = stream.read(&mut buffer).await {
+            received.fetch_add(read, Ordering::Relaxed);
+        }
+    })
+}
+
+/// Benchmark the raw TCP throughput from an iperf3 server, the server sends in reverse mode
+#[tracing::instrument(skip(payload))]
+pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<Iperf3Result, Iperf3Error> {
+    info!("Processing iperf3 job");
+
+    let server = payload
+        .iperf3_server
+        .as_ref()
+        .ok_or_else(|| Iperf3Error::new(ErrorKind::Internal, "iperf3 server is missing"))?;
+    let streams = payload.streams_per_worker.max(1) as usize;
+    let max_duration = Duration::seconds(payload.max_duration_secs);
+    let cookie = generate_cookie();
+
+    let job_start_time = Utc::now();
+
+    // The test is set up before the start time, the data flows once the streams are connected
+    let (address, mut control) = find_free_instance(server, &cookie).await?;
+    write_json(
+        &mut control,
+        &json!({
+            "tcp": true,
+            "omit": 0,
+            "time": payload.max_duration_secs,
+            "num": 0,
+            "blockcount": 0,
+            "parallel": streams,
+            "reverse": true,
+            "len": BLOCK_SIZE,
+            "pacing_timer": 1000,
+            "client_version": CLIENT_VERSION,
+        }),
+    )
+    .await?;
+    expect_state(&mut control, CREATE_STREAMS).await?;
+
+    // Delay the transfer to sync the time on every worker
+    wait_for_start_time(&payload)
+        .await
+        .map_err(|e| Iperf3Error::new(ErrorKind::TimeSync, format!("TimeSyncError: {e}")))?;
+
+    let received: Vec<Arc<AtomicUsize>> = (0..streams)
+        .map(|_| Arc::new(AtomicUsize::new(0)))
+        .collect();
+    let mut readers = Vec::with_capacity(streams);
+    for received in &received {
+        let stream = connect(&address, &cookie).await?;
+        readers.push(spawn_reader(stream, received.clone()));
+    }
+    let total_received = || {
+        received
+            .iter()
+            .map(|received| received.load(Ordering::Relaxed))
+            .sum::<usize>()
+    };
+
+    let result = async {
+        expect_state(&mut control, TEST_START).await?;
+        expect_state(&mut control, TEST_RUNNING).await?;
+
+        let transfer_start_time = Utc::now();
+        let transfer_end_time = transfer_start_time + max_duration;
+        let mut next_log_time =
+            calculate_next_interval(transfer_start_time, payload.log_interval_ms);
+        let mut logged_bytes: usize = 0;
+        let mut second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)> =
+            Vec::new();
+
+        // Save the data for each interval while the server is sending
+        while next_log_time < transfer_end_time {
+            sleep((next_log_time - Utc::now()).to_std().unwrap_or_default()).await;
+
+            let current_time = Utc::now();
+            let total_bytes = total_received();
+            second_by_second_logs.push((
+                current_time,
+                IntervalBytes(total_bytes - logged_bytes),
+                AccumulatingBytes(total_bytes),
+            ));
+            debug!("Time: {:?}, Bytes received: {}", current_time, total_bytes);
+
+            logged_bytes = total_bytes;
+            next_log_time = calculate_next_interval(current_time, payload.log_interval_ms);
+        }
+        sleep(
+            (transfer_end_time - Utc::now())
+                .to_std()
+                .unwrap_or_default(),
+        )
+        .await;
+
+        let end_time = Utc::now();
+        let stream_bytes: Vec<usize> = received
+            .iter()
+            .map(|received| received.load(Ordering::Relaxed))
+            .collect();
+        let total_bytes = stream_bytes.iter().sum::<usize>();
+        let elapsed_secs = (end_time - transfer_start_time).num_milliseconds() as f64 / 1000.0;
+
+        // The readers keep draining the streams until the server stops sending
+        write_state(&mut control, TEST_END).await?;
+        expect_state(&mut control, EXCHANGE_RESULTS).await?;
+        write_json(
+            &mut control,
+            &json!({
+                "cpu_util_total": 0.0,
+                "cpu_util_user": 0.0,
+                "cpu_util_system": 0.0,
+                "sender_has_retransmits": 0,
+                "streams": stream_bytes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, bytes)| json!({
+                        "id": stream_id(index),
+                        "bytes": bytes,
+                        "retransmits": -1,
+                        "jitter": 0,
+                        "errors": 0,
+                        "packets": 0,
+                        "start_time": 0,
+                        "end_time": elapsed_secs,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        )
+        .await?;
+        let server_results = read_json(&mut control).await?;
+        expect_state(&mut control, DISPLAY_RESULTS).await?;
+        write_state(&mut control, IPERF_DONE).await?;
+
+        if total_bytes == 0 {
+            return Err(Iperf3Error::new(ErrorKind::ZeroBytes, "Received 0 bytes"));
+        }
+
+        let server_streams = server_results["streams"].as_array();
+        let server_bytes = server_streams.map(|streams| {
+            streams
+                .iter()
+                .filter_map(|stream| stream["bytes"].as_u64())
+                .sum::<u64>() as usize
+        });
+        let server_retransmits = server_streams
+            .filter(|_| server_results["sender_has_retransmits"].as_i64() == Some(1))
+            .map(|streams| {
+                streams
+                    .iter()
+                    .filter_map(|stream| stream["retransmits"].as_i64())
+                    .sum::<i64>()
+            });
+
+        let iperf3_speed = calculate_speed(total_bytes, elapsed_secs);
+        info!(
+            "Received {} bytes from {} in {:.2} seconds ({:.2} Mbps)",
+            total_bytes, address, elapsed_secs, iperf3_speed
+        );
+
+        Ok(Iperf3Result {
+            server: address.clone(),
+            streams,
+            total_bytes,
+            elapsed_secs,
+            iperf3_speed,
+            job_start_time,
+            transfer_start_time,
+            end_time,
+            second_by_second_logs,
+            server_bytes,
+            server_retransmits,
+        })
+    }
+    .await;
+
+    for reader in readers {
+        reader.abort();
+    }
+
+    result
+}
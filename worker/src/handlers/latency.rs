@@ -0,0 +1,52 @@
+use common::stats::percentile;
+use rabbitmq::LatencyStats;
+
+// Samples kept in the result, long load tests would send millions of them
+const MAX_SAMPLES: usize = 1000;
+
+/// Calculate the distribution of the latency samples collected out of the given number of probes
+///
+/// The distribution is calculated from every sample, at most `MAX_SAMPLES` evenly spread samples
+/// are kept in the result for pooling across workers.
+pub(super) fn calculate_stats(samples: Vec<f64>, probes: usize) -> LatencyStats {
+    if samples.is_empty() {
+        return LatencyStats {
+            loss_percent: 100.0,
+            ..Default::default()
+        };
+    }
+
+    let count = samples.len() as f64;
+    let avg = samples.iter().sum::<f64>() / count;
+    let variance = samples.iter().map(|s| (s - avg).powi(2)).sum::<f64>() / count;
+
+    let jitter = if samples.len() > 1 {
+        samples.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (count - 1.0)
+    } else {
+        0.0
+    };
+
+    let mut sorted = samples.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    LatencyStats {
+        loss_percent: probes.saturating_sub(samples.len()) as f64 / probes.max(1) as f64 * 100.0,
+        stddev: variance.sqrt(),
+        jitter,
+        p50: percentile(&sorted, 50.0),
+        p95: percentile(&sorted, 95.0),
+        p99: percentile(&sorted, 99.0),
+        samples: thin_samples(samples),
+    }
+}
+
+/// Keep at most `MAX_SAMPLES` samples spread evenly over the run, in their original order
+fn thin_samples(samples: Vec<f64>) -> Vec<f64> {
+    if samples.len() <= MAX_SAMPLES {
+        return samples;
+    }
+
+    (0..MAX_SAMPLES)
+        .map(|i| samples[i * samples.len() / MAX_SAMPLES])
+        .collect()
+}
@@ -0,0 +1,251 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration as StdDuration};
+
+use chrono::Utc;
+use common::stats::percentile;
+use rabbitmq::{
+    ConnectionPolicy, ErrorKind, JobMessage, LoadTestError, LoadTestResult, LoadTestSecond,
+};
+use rand::Rng;
+use reqwest::Client;
+use tokio::{
+    task::JoinSet,
+    time::{interval_at, timeout, Instant, MissedTickBehavior},
+};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::{
+    download::{check_range_response, header_map, prepare_request, wait_for_start_time},
+    errors::reqwest_error_kind,
+    latency::calculate_stats,
+    protocol::build_client,
+};
+
+const DEFAULT_READ_SIZE: i64 = 64 * 1024;
+// Requests still running after this time are counted as failed
+const REQUEST_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+/// Outcome of a single request of the load test
+struct RequestOutcome {
+    /// Second of the window the request was sent in
+    second: u64,
+    status_code: Option<u16>,
+    result: Result<f64, ErrorKind>,
+}
+
+/// Request a range at a random offset, returns the time until the whole range was received
+async fn send_request(
+    client: Client,
+    payload: Arc<JobMessage>,
+    read_size: i64,
+) -> (Option<u16>, Result<f64, ErrorKind>) {
+    let range_start =
+        rand::thread_rng().gen_range(payload.start_range..=payload.end_range - read_size + 1);
+    let range_end = range_start + read_size - 1;
+    let request_start = Instant::now();
+
+    let request = async {
+        let response = prepare_request(
+            &client,
+            &payload.url,
+            range_start,
+            range_end,
+            header_map(&payload, "GET"),
+        )
+        .send()
+        .await
+        .map_err(|e| (None, reqwest_error_kind(&e)))?;
+
+        let status_code = response.status().as_u16();
+        check_range_response(&response, range_start, range_end)
+            .map_err(|e| (Some(status_code), e.kind))?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| (Some(status_code), reqwest_error_kind(&e)))?;
+        if body.len() != read_size as usize {
+            return Err((Some(status_code), ErrorKind::ByteCountMismatch));
+        }
+
+        Ok(status_code)
+    };
+
+    match timeout(REQUEST_TIMEOUT, request).await {
+        Ok(Ok(status_code)) => (
+            Some(status_code),
+            Ok(request_start.elapsed().as_secs_f64() * 1000.0),
+        ),
+        Ok(Err((status_code, kind))) => (status_code, Err(kind)),
+        Err(_) => (None, Err(ErrorKind::BodyTimeout)),
+    }
+}
+
+/// Success rate, status codes and latency percentiles of the requests sent in each second
+fn summarize_seconds(outcomes: &[RequestOutcome], seconds: u64) -> Vec<LoadTestSecond> {
+    let mut per_second: Vec<(LoadTestSecond, Vec<f64>)> = (0..seconds)
+        .map(|second| {
+            (
+                LoadTestSecond {
+                    second,
+                    ..Default::default()
+                },
+                Vec::new(),
+            )
+        })
+        .collect();
+
+    for outcome in outcomes {
+        let Some((summary, latencies)) = per_second.get_mut(outcome.second as usize) else {
+            continue;
+        };
+        summary.requests += 1;
+        if let Some(status_code) = outcome.status_code {
+            *summary.status_codes.entry(status_code).or_default() += 1;
+        }
+        if let Ok(latency_ms) = outcome.result {
+            summary.succeeded += 1;
+            latencies.push(latency_ms);
+        }
+    }
+
+    per_second
+        .into_iter()
+        .map(|(mut summary, mut latencies)| {
+            summary.success_rate = summary.succeeded as f64 / summary.requests.max(1) as f64;
+            if !latencies.is_empty() {
+                latencies.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                summary.p50_ms = Some(percentile(&latencies, 50.0));
+                summary.p95_ms = Some(percentile(&latencies, 95.0));
+                summary.p99_ms = Some(percentile(&latencies, 99.0));
+            }
+            summary
+        })
+        .collect()
+}
+
+/// Send range requests at the request rate of the worker for the duration of the window
+///
+/// The load is open loop, requests are sent on schedule even when the earlier ones are still
+/// waiting for the server.
+#[tracing::instrument(skip(payload))]
+pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<LoadTestResult, LoadTestError> {
+    info!("Processing LoadTest job");
+
+    let request_rate = payload
+        .request_rate
+        .filter(|rate| *rate > 0.0)
+        .ok_or_else(|| LoadTestError::new(ErrorKind::Internal, "Request rate is missing"))?;
+    let read_size = payload.read_size.unwrap_or(DEFAULT_READ_SIZE);
+    if payload.end_range - payload.start_range + 1 < read_size {
+        return Err(LoadTestError::new(
+            ErrorKind::Internal,
+            format!("File is smaller than the read size of {read_size} bytes"),
+        ));
+    }
+
+    let window = StdDuration::from_secs(payload.max_duration_secs.max(1) as u64);
+    let http_protocol = payload.http_protocol.unwrap_or_default();
+    let new_client = || {
+        build_client(http_protocol)
+            .map_err(|e| LoadTestError::new(ErrorKind::Internal, format!("ClientError: {e}")))
+    };
+    // The requests share the connection pool unless each gets a fresh connection
+    let fresh_connections = payload.connection_policy == Some(ConnectionPolicy::Fresh);
+    let client = new_client()?;
+
+    let job_start_time = Utc::now();
+
+    // Delay the load test to sync the time on every worker
+    wait_for_start_time(&payload)
+        .await
+        .map_err(|e| LoadTestError::new(ErrorKind::TimeSync, format!("TimeSyncError: {e}")))?;
+
+    let load_test_start_time = Utc::now();
+    let start = Instant::now();
+    let payload = Arc::new(payload);
+
+    // Late ticks are sent right away to keep the rate when the worker falls behind
+    let mut ticker = interval_at(start, StdDuration::from_secs_f64(1.0 / request_rate));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    let mut requests = JoinSet::new();
+    loop {
+        let tick = ticker.tick().await;
+        let offset = tick.duration_since(start);
+        if offset >= window {
+            break;
+        }
+
+        let client = if fresh_connections {
+            new_client()?
+        } else {
+            client.clone()
+        };
+        let payload = payload.clone();
+        requests.spawn(async move {
+            let (status_code, result) = send_request(client, payload, read_size).await;
+            RequestOutcome {
+                second: offset.as_secs(),
+                status_code,
+                result,
+            }
+        });
+    }
+
+    let mut outcomes = Vec::with_capacity(requests.len());
+    while let Some(outcome) = requests.join_next().await {
+        match outcome {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(e) => debug!("Load test request task failed: {}", e),
+        }
+    }
+    let end_time = Utc::now();
+
+    if outcomes.is_empty() {
+        return Err(LoadTestError::new(
+            ErrorKind::Internal,
+            "No request was sent",
+        ));
+    }
+
+    let mut status_codes: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut errors: BTreeMap<String, usize> = BTreeMap::new();
+    let mut latencies = Vec::new();
+    for outcome in &outcomes {
+        if let Some(status_code) = outcome.status_code {
+            *status_codes.entry(status_code).or_default() += 1;
+        }
+        match &outcome.result {
+            Ok(latency_ms) => latencies.push(*latency_ms),
+            Err(kind) => *errors.entry(kind.name()).or_default() += 1,
+        }
+    }
+
+    let succeeded = latencies.len();
+    let success_rate = succeeded as f64 / outcomes.len() as f64;
+    let achieved_rate = succeeded as f64 / window.as_secs_f64();
+
+    info!(
+        "Sent {} requests at {:.2} req/s, {} succeeded ({:.2} req/s)",
+        outcomes.len(),
+        request_rate,
+        succeeded,
+        achieved_rate
+    );
+
+    Ok(LoadTestResult {
+        request_rate,
+        read_size: read_size as usize,
+        requests: outcomes.len(),
+        succeeded,
+        success_rate,
+        achieved_rate,
+        job_start_time,
+        load_test_start_time,
+        end_time,
+        status_codes,
+        errors,
+        per_second: summarize_seconds(&outcomes, window.as_secs()),
+        latency_ms: calculate_stats(latencies, outcomes.len()),
+    })
+}
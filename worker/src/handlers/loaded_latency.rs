@@ -0,0 +1,111 @@
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Duration, Utc};
+use rabbitmq::{JobMessage, LoadedLatency, PingMethod};
+use surge_ping::{PingSequence, Pinger};
+use tokio::{sync::oneshot, time::sleep};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use super::{
+    download::{calculate_next_interval, wait_for_start_time},
+    ping::{icmp_pinger, resolve_url, tcp_connect_time},
+};
+
+enum Probe {
+    Icmp(Pinger),
+    Tcp(SocketAddr),
+}
+
+/// Probe the latency on every log interval while the download is running
+///
+/// The probes are aligned with the interval logs of the download, so the loaded latency can be
+/// compared with the throughput at the same time.
+#[tracing::instrument(skip(payload, download_finished))]
+pub async fn process(
+    job_id: Uuid,
+    payload: JobMessage,
+    mut download_finished: oneshot::Receiver<()>,
+) -> Option<LoadedLatency> {
+    if !payload.measure_loaded_latency {
+        return None;
+    }
+
+    info!("Processing loaded latency job");
+
+    let (ip_address, port) = resolve_url(&payload.url)
+        .inspect_err(|e| error!("Failed to resolve URL: {}", e.error))
+        .ok()?;
+    // A probe must not take longer than the interval to keep the samples aligned
+    let probe_timeout = Duration::milliseconds(payload.log_interval_ms)
+        .to_std()
+        .ok()?;
+
+    let (method, mut probe) = match payload.ping_method {
+        Some(PingMethod::Tcp) => (
+            PingMethod::Tcp,
+            Probe::Tcp(SocketAddr::new(ip_address, port)),
+        ),
+        method => match icmp_pinger(ip_address).await {
+            Ok(mut pinger) => {
+                pinger.timeout(probe_timeout);
+                (PingMethod::Icmp, Probe::Icmp(pinger))
+            }
+            // ICMP needs CAP_NET_RAW, fall back to TCP connect unless ICMP was chosen explicitly
+            Err(e) if method.is_none() => {
+                info!("ICMP ping failed, falling back to TCP connect: {}", e.error);
+                (
+                    PingMethod::Tcp,
+                    Probe::Tcp(SocketAddr::new(ip_address, port)),
+                )
+            }
+            Err(e) => {
+                error!("Failed to create ICMP pinger: {}", e.error);
+                return None;
+            }
+        },
+    };
+
+    // Start probing together with the download
+    wait_for_start_time(&payload)
+        .await
+        .inspect_err(|e| error!("Failed to wait for the start time: {}", e))
+        .ok()?;
+
+    let deadline = payload.download_start_time + Duration::seconds(payload.max_duration_secs);
+    let mut samples: Vec<(DateTime<Utc>, Option<f64>)> = Vec::new();
+    let mut next_probe_time = calculate_next_interval(Utc::now(), payload.log_interval_ms);
+
+    for seq in 0u16.. {
+        let sleep_duration = (next_probe_time - Utc::now()).to_std().unwrap_or_default();
+
+        tokio::select! {
+            _ = &mut download_finished => break,
+            _ = sleep(sleep_duration) => {}
+        }
+
+        let probe_time = Utc::now();
+        if probe_time >= deadline {
+            break;
+        }
+
+        let rtt = match &mut probe {
+            Probe::Icmp(pinger) => pinger
+                .ping(PingSequence(seq), &[6, 6, 6])
+                .await
+                .inspect_err(|e| error!("Failed to ping host: {}", e))
+                .ok()
+                .map(|(_, duration)| duration),
+            Probe::Tcp(socket_addr) => tcp_connect_time(*socket_addr, probe_timeout).await,
+        };
+        let rtt_ms = rtt.map(|duration| duration.as_secs_f64() * 1000.0);
+        debug!("Time: {:?}, Loaded latency: {:?} ms", probe_time, rtt_ms);
+
+        samples.push((probe_time, rtt_ms));
+        next_probe_time = calculate_next_interval(Utc::now(), payload.log_interval_ms);
+    }
+
+    info!("Finished loaded latency job with {} probes", samples.len());
+
+    Some(LoadedLatency { method, samples })
+}
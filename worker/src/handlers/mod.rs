@@ -1,3 +1,14 @@
+pub mod connection_timing;
 pub mod download;
+mod errors;
 pub mod head;
+pub mod iperf3;
+mod latency;
+pub mod load_test;
+pub mod loaded_latency;
 pub mod ping;
+mod protocol;
+pub mod random_access;
+pub mod retrieval;
+pub mod soak;
+pub mod upload;
@@ -1,14 +1,29 @@
-use std::net::{IpAddr, ToSocketAddrs};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use color_eyre::Result;
-use rabbitmq::{JobMessage, PingError, PingResult};
+use rabbitmq::{ErrorKind, JobMessage, PingError, PingMethod, PingResult};
 use rand::random;
-use surge_ping::{Client, Config, PingIdentifier, PingSequence, ICMP};
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, Pinger, ICMP};
+use tokio::{
+    net::TcpStream,
+    time::{timeout, Instant},
+};
 use tracing::{debug, error, info};
 use url::Url;
 use uuid::Uuid;
 
+use super::latency::calculate_stats;
+
+const SEQ_MAX: u16 = 10;
+const PACKETS_THRESHOLD: usize = SEQ_MAX as usize / 2;
+// Timeout of a single TCP connect probe
+const TCP_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+// Timeout of a single ICMP probe, the default of surge-ping
+const ICMP_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+// Probes without any reply after which the fallback gives up on ICMP
+const ICMP_FALLBACK_PROBES: usize = 3;
+
 #[tracing::instrument(skip(payload))]
 pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<PingResult, PingError> {
     info!("Processing PING job");
@@ -18,50 +33,168 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<PingResult, Pi
 
     debug!("now: {} loop_deadline: {}", Utc::now(), loop_deadline);
 
+    let (ip_address, port) = resolve_url(&payload.url)?;
+
+    let (method, (latencies, probes)) = match payload.ping_method {
+        Some(PingMethod::Icmp) => {
+            let (latencies, probes) = ping_icmp(ip_address, loop_deadline, false).await?;
+            (PingMethod::Icmp, check_packets_lost(latencies, probes)?)
+        }
+        Some(PingMethod::Tcp) => (
+            PingMethod::Tcp,
+            ping_tcp(SocketAddr::new(ip_address, port), loop_deadline).await?,
+        ),
+        // ICMP needs CAP_NET_RAW and is dropped by some hosts, fall back to TCP connect
+        None => match ping_icmp(ip_address, loop_deadline, true).await {
+            // The host answers ICMP, the lost probes are loss
+            Ok((latencies, probes)) => (PingMethod::Icmp, check_packets_lost(latencies, probes)?),
+            Err(e) => {
+                info!("ICMP ping failed, falling back to TCP connect: {}", e.error);
+                (
+                    PingMethod::Tcp,
+                    ping_tcp(SocketAddr::new(ip_address, port), loop_deadline).await?,
+                )
+            }
+        },
+    };
+
+    // Calculate the average, min, and max latencies
+    let avg_latency: f64 = latencies.iter().sum::<f64>() / latencies.len() as f64;
+    let min_latency: f64 = *latencies
+        .iter()
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap();
+    let max_latency: f64 = *latencies
+        .iter()
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap();
+
+    debug!("Latency Statistics ({:?}):", method);
+    debug!("Average: {:.2} ms", avg_latency);
+    debug!("Min: {:.2} ms", min_latency);
+    debug!("Max: {:.2} ms", max_latency);
+
+    let stats = calculate_stats(latencies, probes);
+    debug!(
+        "Loss: {:.2} %, Jitter: {:.2}",
+        stats.loss_percent, stats.jitter
+    );
+
+    Ok(PingResult {
+        avg: avg_latency,
+        min: min_latency,
+        max: max_latency,
+        ip_address: ip_address.to_string(),
+        method,
+        stats,
+    })
+}
+
+/// Resolve the host of the URL to an IP address, returns it with the port of the URL
+pub(super) fn resolve_url(url: &str) -> Result<(IpAddr, u16), PingError> {
     // Parse the URL and extract the host
-    let url = Url::parse(&payload.url).map_err(|e| PingError {
-        error: format!("UrlParseError: {e}"),
-    })?;
-    let host = url.host_str().ok_or(PingError {
-        error: "Failed to extract host from URL".to_string(),
-    })?;
+    let url = Url::parse(url)
+        .map_err(|e| PingError::new(ErrorKind::InvalidUrl, format!("UrlParseError: {e}")))?;
+    let host = url.host_str().ok_or(PingError::new(
+        ErrorKind::InvalidUrl,
+        "Failed to extract host from URL",
+    ))?;
+    let port = url.port_or_known_default().ok_or(PingError::new(
+        ErrorKind::InvalidUrl,
+        "Failed to extract port from URL",
+    ))?;
 
     // Resolve the host to an IP address
     let ip_address: IpAddr = (host, 0)
         .to_socket_addrs()
-        .map_err(|_| PingError {
-            error: "Failed to extract IP address from socket addr".to_string(),
-        })?
+        .map_err(|e| PingError::new(ErrorKind::Dns, format!("Failed to resolve host: {e}")))?
         .map(|socket_addr| socket_addr.ip())
         .collect::<Vec<IpAddr>>()
         .first()
         .cloned() // Convert Option<&T> to Option<T>
-        .ok_or(PingError {
-            error: "Failed to extract IP address from socket addr".to_string(),
-        })?;
+        .ok_or(PingError::new(
+            ErrorKind::Dns,
+            "Failed to extract IP address from socket addr",
+        ))?;
 
+    Ok((ip_address, port))
+}
+
+/// Create an ICMP pinger for the IP address, fails without raw socket access
+pub(super) async fn icmp_pinger(ip_address: IpAddr) -> Result<Pinger, PingError> {
     let config = match ip_address {
         IpAddr::V4(_) => Config::default(),
         IpAddr::V6(_) => Config::builder().kind(ICMP::V6).build(),
     };
-    let client = Client::new(&config).map_err(|e| PingError {
-        error: format!("SurgePingClientError: {e}"),
-    })?;
-    let mut pinger = client.pinger(ip_address, PingIdentifier(random())).await;
+    let client = Client::new(&config)
+        .map_err(|e| PingError::new(ErrorKind::PingClient, format!("SurgePingClientError: {e}")))?;
+
+    Ok(client.pinger(ip_address, PingIdentifier(random())).await)
+}
+
+/// Time a single TCP handshake, the connection is closed right away
+pub(super) async fn tcp_connect_time(
+    socket_addr: SocketAddr,
+    connect_timeout: std::time::Duration,
+) -> Option<std::time::Duration> {
+    // The connect completes once the SYN-ACK arrives
+    let start = Instant::now();
+    match timeout(connect_timeout, TcpStream::connect(socket_addr)).await {
+        Ok(Ok(_)) => Some(start.elapsed()),
+        Ok(Err(e)) => {
+            error!("Failed to connect to host: {}", e);
+            None
+        }
+        Err(_) => {
+            error!("Connect to host timed out");
+            None
+        }
+    }
+}
+
+/// Time left until the deadline for a probe, at most the probe timeout
+fn probe_timeout(
+    loop_deadline: DateTime<Utc>,
+    max_timeout: std::time::Duration,
+) -> std::time::Duration {
+    (loop_deadline - Utc::now())
+        .to_std()
+        .unwrap_or_default()
+        .min(max_timeout)
+}
+
+/// Measure the round trip time with ICMP echo requests
+///
+/// As the first method of the fallback it gives up when none of the first probes gets a reply, a
+/// host dropping ICMP would take the time of every probe and leave none to the TCP connect probes.
+/// Once a reply arrived the lost probes are counted as loss.
+async fn ping_icmp(
+    ip_address: IpAddr,
+    loop_deadline: DateTime<Utc>,
+    fallback: bool,
+) -> Result<(Vec<f64>, usize), PingError> {
+    let mut pinger = icmp_pinger(ip_address).await?;
 
     let mut latencies: Vec<f64> = Vec::new();
-    let seq_max = 10;
-    let packets_threshold = seq_max / 2;
+    let mut probes: usize = 0;
 
-    for seq in 0..seq_max {
+    for seq in 0..SEQ_MAX {
         // Check deadline
         if Utc::now() >= loop_deadline {
             info!("Loop deadline reached, aborting the loop");
             break;
         }
+        probes += 1;
 
+        pinger.timeout(probe_timeout(loop_deadline, ICMP_PROBE_TIMEOUT));
         let (_, duration) = match pinger.ping(PingSequence(seq), &[6, 6, 6]).await {
             Ok((packet, duration)) => (packet, duration),
+            Err(e) if fallback && latencies.is_empty() && probes >= ICMP_FALLBACK_PROBES => {
+                return Err(PingError::new(
+                    ErrorKind::PacketLoss,
+                    format!("No reply to the first {probes} ICMP probes: {e}"),
+                ));
+            }
             Err(e) => {
                 error!("Failed to ping host: {}", e);
                 continue;
@@ -70,33 +203,53 @@ pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<PingResult, Pi
         latencies.push(duration.as_secs_f64());
     }
 
-    // Check if we have at least half of the packets
-    if latencies.len() < packets_threshold.into() {
-        return Err(PingError {
-            error: "Too many packets lost".to_string(),
-        });
+    if fallback && latencies.is_empty() {
+        return Err(PingError::new(
+            ErrorKind::PacketLoss,
+            "No reply to the ICMP probes",
+        ));
     }
 
-    // Calculate the average, min, and max latencies
-    let avg_latency: f64 = latencies.iter().sum::<f64>() / latencies.len() as f64;
-    let min_latency: f64 = *latencies
-        .iter()
-        .min_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap();
-    let max_latency: f64 = *latencies
-        .iter()
-        .max_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap();
+    Ok((latencies, probes))
+}
 
-    debug!("Latency Statistics:");
-    debug!("Average: {:.2} ms", avg_latency);
-    debug!("Min: {:.2} ms", min_latency);
-    debug!("Max: {:.2} ms", max_latency);
+/// Measure the round trip time with the TCP handshake, works without raw socket access
+async fn ping_tcp(
+    socket_addr: SocketAddr,
+    loop_deadline: DateTime<Utc>,
+) -> Result<(Vec<f64>, usize), PingError> {
+    let mut latencies: Vec<f64> = Vec::new();
+    let mut probes: usize = 0;
 
-    Ok(PingResult {
-        avg: avg_latency,
-        min: min_latency,
-        max: max_latency,
-        ip_address: ip_address.to_string(),
-    })
+    for _ in 0..SEQ_MAX {
+        // Check deadline
+        if Utc::now() >= loop_deadline {
+            info!("Loop deadline reached, aborting the loop");
+            break;
+        }
+        probes += 1;
+
+        if let Some(duration) = tcp_connect_time(
+            socket_addr,
+            probe_timeout(loop_deadline, TCP_CONNECT_TIMEOUT),
+        )
+        .await
+        {
+            latencies.push(duration.as_secs_f64());
+        }
+    }
+
+    check_packets_lost(latencies, probes)
+}
+
+/// Check if we have at least half of the packets
+fn check_packets_lost(latencies: Vec<f64>, probes: usize) -> Result<(Vec<f64>, usize), PingError> {
+    if latencies.len() < PACKETS_THRESHOLD {
+        return Err(PingError::new(
+            ErrorKind::PacketLoss,
+            "Too many packets lost",
+        ));
+    }
+
+    Ok((latencies, probes))
 }
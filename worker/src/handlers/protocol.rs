@@ -0,0 +1,277 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use color_eyre::{eyre::ContextCompat, Result};
+use native_tls::TlsConnector as NativeTlsConnector;
+use rabbitmq::{HttpProtocol, ProtocolInfo};
+use reqwest::{header::ALT_SVC, Client, Response};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+    time::timeout,
+};
+use tokio_native_tls::TlsConnector;
+use tracing::{debug, info};
+use url::Url;
+
+// The ServerHello of a handshake fits in a single TLS record of at most 16 KiB
+const MAX_RECORDED_BYTES: usize = 5 + 16 * 1024;
+// Extension of TLS 1.3 selecting the negotiated version
+const SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// Negotiated TLS parameters of a probe connection
+#[derive(Default)]
+pub(super) struct TlsDetails {
+    alpn: Option<String>,
+    tls_version: Option<String>,
+}
+
+/// Build the HTTP client offering the protocol
+pub(super) fn build_client(protocol: HttpProtocol) -> reqwest::Result<Client> {
+    let builder = Client::builder();
+    let builder = match protocol {
+        HttpProtocol::Http1 => builder.http1_only(),
+        HttpProtocol::Http2 => builder,
+        // QUIC support of reqwest is unstable, the Alt-Svc header shows whether HTTP/3 was offered
+        HttpProtocol::Http3 => {
+            info!("HTTP/3 is not available on this worker, falling back to HTTP/2");
+            builder
+        }
+    };
+
+    builder.build()
+}
+
+/// Negotiate TLS on a probe connection with the ALPN protocols of the preference
+///
+/// The HTTP client doesn't expose the handshake, a probe offering the same protocols gets the
+/// same answer from the server.
+pub(super) async fn negotiate_tls(
+    url: &str,
+    protocol: HttpProtocol,
+    connect_timeout: Duration,
+) -> Result<TlsDetails> {
+    let url = Url::parse(url)?;
+    if url.scheme() != "https" {
+        return Ok(TlsDetails::default());
+    }
+
+    timeout(connect_timeout, handshake(&url, protocol)).await?
+}
+
+async fn handshake(url: &Url, protocol: HttpProtocol) -> Result<TlsDetails> {
+    let host = url.host_str().context("Failed to extract host from URL")?;
+    let port = url
+        .port_or_known_default()
+        .context("Failed to extract port from URL")?;
+
+    let stream = TcpStream::connect((host, port)).await?;
+    let connector = TlsConnector::from(
+        NativeTlsConnector::builder()
+            .request_alpns(protocol.alpn_protocols())
+            .build()?,
+    );
+    let tls_stream = connector
+        .connect(host, RecordingStream::new(stream))
+        .await?;
+
+    let details = TlsDetails {
+        alpn: tls_stream
+            .get_ref()
+            .negotiated_alpn()?
+            .map(|alpn| String::from_utf8_lossy(&alpn).to_string()),
+        tls_version: server_hello_version(&tls_stream.get_ref().get_ref().get_ref().received),
+    };
+    debug!(
+        "Negotiated ALPN: {:?}, TLS version: {:?}",
+        details.alpn, details.tls_version
+    );
+
+    Ok(details)
+}
+
+/// TCP stream of the probe that keeps the beginning of the received data
+///
+/// native-tls doesn't expose the negotiated version, the ServerHello at the start of the
+/// received data carries it.
+struct RecordingStream {
+    inner: TcpStream,
+    received: Vec<u8>,
+}
+
+impl RecordingStream {
+    fn new(inner: TcpStream) -> Self {
+        Self {
+            inner,
+            received: Vec::new(),
+        }
+    }
+}
+
+impl AsyncRead for RecordingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        let space = MAX_RECORDED_BYTES.saturating_sub(self.received.len());
+        let received = &buf.filled()[filled..];
+        self.received
+            .extend_from_slice(&received[..received.len().min(space)]);
+
+        result
+    }
+}
+
+impl AsyncWrite for RecordingStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// TLS version selected by the ServerHello at the start of the data, named the way OpenSSL
+/// names it
+///
+/// TLS 1.3 keeps 1.2 as the legacy version, the supported_versions extension selects 1.3.
+fn server_hello_version(data: &[u8]) -> Option<String> {
+    let read_u16 = |position: usize| {
+        Some(u16::from_be_bytes([
+            *data.get(position)?,
+            *data.get(position + 1)?,
+        ]))
+    };
+
+    // Handshake record header of 5 bytes, then the ServerHello with a 4 byte header
+    if data.first() != Some(&0x16) || data.get(5) != Some(&0x02) {
+        return None;
+    }
+
+    // Legacy version and 32 bytes of random, then the session id, cipher suite and compression
+    let mut version = read_u16(9)?;
+    let session_id_length = *data.get(9 + 34)? as usize;
+    let mut position = 9 + 34 + 1 + session_id_length + 2 + 1;
+    if let Some(extensions_length) = read_u16(position) {
+        let extensions_end = position + 2 + extensions_length as usize;
+        position += 2;
+        while position + 4 <= extensions_end {
+            let extension_type = read_u16(position)?;
+            let extension_length = read_u16(position + 2)? as usize;
+            if extension_type == SUPPORTED_VERSIONS && extension_length == 2 {
+                version = read_u16(position + 4)?;
+            }
+            position += 4 + extension_length;
+        }
+    }
+
+    let name = match version {
+        0x0301 => "TLSv1",
+        0x0302 => "TLSv1.1",
+        0x0303 => "TLSv1.2",
+        0x0304 => "TLSv1.3",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Protocol of the response, the TLS details are added once the probe finished
+pub(super) fn protocol_info(response: &Response) -> ProtocolInfo {
+    ProtocolInfo {
+        http_version: format!("{:?}", response.version()),
+        alpn: None,
+        tls_version: None,
+        alt_svc: response
+            .headers()
+            .get(ALT_SVC)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+/// Add the negotiated TLS parameters to the protocol of the response
+pub(super) fn with_tls_details(mut info: ProtocolInfo, tls_details: &TlsDetails) -> ProtocolInfo {
+    info.alpn.clone_from(&tls_details.alpn);
+    info.tls_version.clone_from(&tls_details.tls_version);
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_hello(legacy_version: [u8; 2], extensions: &[u8]) -> Vec<u8> {
+        let mut hello = legacy_version.to_vec();
+        hello.extend_from_slice(&[0xab; 32]);
+        // Session id of 32 bytes, TLS_AES_128_GCM_SHA256 and no compression
+        hello.push(32);
+        hello.extend_from_slice(&[0xcd; 32]);
+        hello.extend_from_slice(&[0x13, 0x01, 0x00]);
+        hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        hello.extend_from_slice(extensions);
+
+        let mut handshake = vec![0x02, 0x00];
+        handshake.extend_from_slice(&(hello.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(&hello);
+
+        let mut record = vec![0x16, 0x03, 0x03];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn tls_1_2_server_hello() {
+        // renegotiation_info
+        let extensions = [0xff, 0x01, 0x00, 0x01, 0x00];
+
+        assert_eq!(
+            server_hello_version(&server_hello([0x03, 0x03], &extensions)).as_deref(),
+            Some("TLSv1.2")
+        );
+        assert_eq!(
+            server_hello_version(&server_hello([0x03, 0x03], &[])).as_deref(),
+            Some("TLSv1.2")
+        );
+    }
+
+    #[test]
+    fn tls_1_3_server_hello() {
+        // key_share, then supported_versions selecting TLS 1.3
+        let extensions = [
+            0x00, 0x33, 0x00, 0x04, 0x00, 0x1d, 0x00, 0x00, 0x00, 0x2b, 0x00, 0x02, 0x03, 0x04,
+        ];
+
+        assert_eq!(
+            server_hello_version(&server_hello([0x03, 0x03], &extensions)).as_deref(),
+            Some("TLSv1.3")
+        );
+    }
+
+    #[test]
+    fn not_a_server_hello() {
+        let mut alert = server_hello([0x03, 0x03], &[]);
+        alert[0] = 0x15;
+
+        assert_eq!(server_hello_version(&alert), None);
+        assert_eq!(server_hello_version(&[0x16, 0x03]), None);
+        assert_eq!(server_hello_version(&server_hello([0x7f, 0x00], &[])), None);
+    }
+}
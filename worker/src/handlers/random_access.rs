@@ -0,0 +1,232 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::future::join_all;
+use rabbitmq::{ConnectionPolicy, ErrorKind, JobMessage, RandomAccessError, RandomAccessResult};
+use rand::Rng;
+use reqwest::Client;
+use tokio::time::{timeout, Instant};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::{
+    download::{check_range_response, header_map, prepare_request, wait_for_start_time},
+    errors::reqwest_error_kind,
+    latency::calculate_stats,
+    protocol::build_client,
+};
+
+// Keeps the samples of a fast server within a reasonable result size
+const MAX_REQUESTS: usize = 10_000;
+const DEFAULT_READ_SIZE: i64 = 64 * 1024;
+
+/// Outcome of the requests of one requester
+#[derive(Default)]
+struct RequesterResult {
+    ttfb_ms: Vec<f64>,
+    total_bytes: usize,
+    failed_requests: usize,
+    last_error: Option<RandomAccessError>,
+}
+
+/// Request a range at a random offset and wait for the whole range, returns the time to first
+/// byte in milliseconds and the received bytes
+async fn read_random_range(
+    client: &Client,
+    payload: &JobMessage,
+    read_size: i64,
+    deadline: DateTime<Utc>,
+) -> Result<(f64, usize), RandomAccessError> {
+    let range_start =
+        rand::thread_rng().gen_range(payload.start_range..=payload.end_range - read_size + 1);
+    let range_end = range_start + read_size - 1;
+    let remaining = || (deadline - Utc::now()).to_std().unwrap_or_default();
+
+    let request_start = Instant::now();
+    let request = prepare_request(
+        client,
+        &payload.url,
+        range_start,
+        range_end,
+        header_map(payload, "GET"),
+    );
+    // A server that never answers would hold the requester past the deadline
+    let mut response = match timeout(remaining(), request.send()).await {
+        Ok(response) => response.map_err(|e| {
+            RandomAccessError::new(reqwest_error_kind(&e), format!("RequestError: {e}"))
+        })?,
+        Err(_) => {
+            return Err(RandomAccessError::new(
+                ErrorKind::BodyTimeout,
+                "BodyTimeout: no response before the maximum duration",
+            ))
+        }
+    };
+    check_range_response(&response, range_start, range_end)?;
+
+    let mut ttfb_ms = None;
+    let mut bytes: usize = 0;
+    loop {
+        let chunk = match timeout(remaining(), response.chunk()).await {
+            Ok(Ok(Some(chunk))) => chunk,
+            Ok(Ok(None)) => break,
+            Ok(Err(e)) => {
+                return Err(RandomAccessError::new(
+                    reqwest_error_kind(&e),
+                    format!("ChunkError: {e}"),
+                ))
+            }
+            Err(_) => {
+                return Err(RandomAccessError::new(
+                    ErrorKind::BodyTimeout,
+                    "BodyTimeout: range not received before the maximum duration",
+                ))
+            }
+        };
+        ttfb_ms.get_or_insert(request_start.elapsed().as_secs_f64() * 1000.0);
+        bytes += chunk.len();
+    }
+
+    if bytes != read_size as usize {
+        return Err(RandomAccessError::new(
+            ErrorKind::ByteCountMismatch,
+            format!("ByteCountMismatch: expected {read_size} bytes, got {bytes}"),
+        ));
+    }
+
+    Ok((ttfb_ms.unwrap_or_default(), bytes))
+}
+
+/// Benchmark the latency of small range requests at random offsets of the file
+#[tracing::instrument(skip(payload))]
+pub async fn process(
+    job_id: Uuid,
+    payload: JobMessage,
+) -> Result<RandomAccessResult, RandomAccessError> {
+    info!("Processing RandomAccess job");
+
+    let read_size = payload.read_size.unwrap_or(DEFAULT_READ_SIZE);
+    if payload.end_range - payload.start_range + 1 < read_size {
+        return Err(RandomAccessError::new(
+            ErrorKind::Internal,
+            format!("File is smaller than the read size of {read_size} bytes"),
+        ));
+    }
+
+    // Random access deadline, job will succeed but won't send requests after this duration
+    let max_duration = Duration::seconds(payload.max_duration_secs);
+    let requesters = payload.streams_per_worker.max(1) as usize;
+    let http_protocol = payload.http_protocol.unwrap_or_default();
+    let new_client = || {
+        build_client(http_protocol)
+            .map_err(|e| RandomAccessError::new(ErrorKind::Internal, format!("ClientError: {e}")))
+    };
+
+    // Every requester keeps its connection unless the connections are shared or fresh for each request
+    let shared_client = match payload.connection_policy {
+        Some(ConnectionPolicy::Reuse) => Some(new_client()?),
+        Some(ConnectionPolicy::Fresh) | None => None,
+    };
+    let clients = (0..requesters)
+        .map(|_| match &shared_client {
+            Some(client) => Ok(client.clone()),
+            None => new_client(),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let fresh_connections = payload.connection_policy == Some(ConnectionPolicy::Fresh);
+
+    let job_start_time = Utc::now();
+
+    // Delay the requests to sync the time on every worker
+    wait_for_start_time(&payload)
+        .await
+        .map_err(|e| RandomAccessError::new(ErrorKind::TimeSync, format!("TimeSyncError: {e}")))?;
+
+    let random_access_start_time = Utc::now();
+    let deadline = random_access_start_time + max_duration;
+    let issued_requests = Arc::new(AtomicUsize::new(0));
+
+    let requester_results = join_all(clients.into_iter().map(|mut client| {
+        let payload = &payload;
+        let issued_requests = issued_requests.clone();
+        async move {
+            let mut result = RequesterResult::default();
+            while Utc::now() < deadline
+                && issued_requests.fetch_add(1, Ordering::Relaxed) < MAX_REQUESTS
+            {
+                if fresh_connections {
+                    client = match new_client() {
+                        Ok(client) => client,
+                        Err(e) => {
+                            result.last_error = Some(e);
+                            break;
+                        }
+                    };
+                }
+
+                match read_random_range(&client, payload, read_size, deadline).await {
+                    Ok((ttfb_ms, bytes)) => {
+                        result.ttfb_ms.push(ttfb_ms);
+                        result.total_bytes += bytes;
+                    }
+                    // Requests cut by the deadline don't count as failed
+                    Err(e) if e.kind == ErrorKind::BodyTimeout && Utc::now() >= deadline => {}
+                    Err(e) => {
+                        debug!("Random range request failed: {}", e.error);
+                        result.failed_requests += 1;
+                        result.last_error = Some(e);
+                    }
+                }
+            }
+            result
+        }
+    }))
+    .await;
+
+    let end_time = Utc::now();
+    let elapsed_secs = (end_time - random_access_start_time).num_milliseconds() as f64 / 1000.0;
+
+    let mut ttfb_ms = Vec::new();
+    let mut total_bytes: usize = 0;
+    let mut failed_requests: usize = 0;
+    let mut last_error = None;
+    for result in requester_results {
+        ttfb_ms.extend(result.ttfb_ms);
+        total_bytes += result.total_bytes;
+        failed_requests += result.failed_requests;
+        last_error = result.last_error.or(last_error);
+    }
+
+    if ttfb_ms.is_empty() {
+        return Err(last_error.unwrap_or(RandomAccessError::new(
+            ErrorKind::ZeroBytes,
+            "No range request completed",
+        )));
+    }
+
+    let requests = ttfb_ms.len();
+    let requests_per_sec = requests as f64 / elapsed_secs;
+    let avg_ttfb_ms = ttfb_ms.iter().sum::<f64>() / requests as f64;
+
+    info!(
+        "Completed {} range requests of {} bytes ({} failed) in {:.2} seconds ({:.2} req/s, {:.2} ms average TTFB)",
+        requests, read_size, failed_requests, elapsed_secs, requests_per_sec, avg_ttfb_ms
+    );
+
+    Ok(RandomAccessResult {
+        read_size: read_size as usize,
+        requests,
+        failed_requests,
+        total_bytes,
+        elapsed_secs,
+        requests_per_sec,
+        job_start_time,
+        random_access_start_time,
+        end_time,
+        avg_ttfb_ms,
+        ttfb_ms: calculate_stats(ttfb_ms, requests + failed_requests),
+    })
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::Result;
+use rabbitmq::{
+    AccumulatingBytes, ErrorKind, IntervalBytes, JobMessage, RetrievalError, RetrievalResult,
+};
+use reqwest::{
+    header::{ACCEPT, CONTENT_TYPE, USER_AGENT},
+    Client, RequestBuilder, StatusCode,
+};
+use tokio::time::timeout;
+use tracing::{debug, info};
+use url::Url;
+use uuid::Uuid;
+
+use super::{
+    download::{calculate_next_interval, calculate_speed, wait_for_start_time},
+    errors::reqwest_error_kind,
+};
+use crate::car::{CarError, CarReader};
+
+const CAR_CONTENT_TYPE: &str = "application/vnd.ipld.car";
+
+/// Prepare the trustless gateway request for the CAR of the content
+fn prepare_request(payload: &JobMessage) -> Result<RequestBuilder, RetrievalError> {
+    const USER_AGENT_STR: &str = "curl/7.68.0";
+
+    let mut url = Url::parse(&payload.url)
+        .map_err(|e| RetrievalError::new(ErrorKind::InvalidUrl, format!("UrlParseError: {e}")))?;
+    url.query_pairs_mut()
+        .append_pair("dag-scope", payload.dag_scope.unwrap_or_default().as_str());
+
+    Ok(Client::new()
+        .get(url)
+        .header(USER_AGENT, USER_AGENT_STR)
+        .header(ACCEPT, format!("{CAR_CONTENT_TYPE}; version=1")))
+}
+
+/// Benchmark the retrieval of the content over the trustless gateway protocol
+#[tracing::instrument(skip(payload))]
+pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<RetrievalResult, RetrievalError> {
+    info!("Processing Retrieval job");
+
+    // Retrieval deadline, job will succeed but won't retrieve more than this duration
+    let max_duration = Duration::seconds(payload.max_duration_secs);
+    let request = prepare_request(&payload)?;
+
+    let job_start_time = Utc::now();
+
+    // Delay the retrieval execution to sync the time on every worker
+    wait_for_start_time(&payload)
+        .await
+        .map_err(|e| RetrievalError::new(ErrorKind::TimeSync, format!("TimeSyncError: {e}")))?;
+
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| RetrievalError::new(reqwest_error_kind(&e), format!("RequestError: {e}")))?;
+
+    if response.status() != StatusCode::OK {
+        return Err(RetrievalError::new(
+            ErrorKind::HttpStatus {
+                code: response.status().as_u16(),
+            },
+            format!("RequestFailed: {}", response.status()),
+        ));
+    }
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !content_type.starts_with(CAR_CONTENT_TYPE) {
+        return Err(RetrievalError::new(
+            ErrorKind::UnexpectedContentType,
+            format!("UnexpectedContentType: {content_type}"),
+        ));
+    }
+
+    let time_to_first_byte_ms = (Utc::now() - job_start_time).num_milliseconds() as f64;
+    debug!("Time to first byte: {} ms", time_to_first_byte_ms);
+
+    let retrieval_start_time = Utc::now();
+    let mut next_log_time = calculate_next_interval(retrieval_start_time, payload.log_interval_ms);
+
+    let mut car_reader = CarReader::new();
+    let mut bytes: usize = 0;
+    let mut total_bytes: usize = 0;
+    let mut blocks: usize = 0;
+    let mut verified_blocks: usize = 0;
+    let mut block_bytes: usize = 0;
+    let mut second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)> =
+        Vec::new();
+
+    let remaining = |current_time: DateTime<Utc>| {
+        (max_duration - (current_time - retrieval_start_time))
+            .to_std()
+            .unwrap_or_default()
+    };
+
+    let mut reached_deadline = false;
+    loop {
+        let chunk = match timeout(remaining(Utc::now()), response.chunk()).await {
+            Ok(Ok(Some(chunk))) => chunk,
+            Ok(Ok(None)) => break,
+            Ok(Err(e)) => {
+                return Err(RetrievalError::new(
+                    reqwest_error_kind(&e),
+                    format!("ChunkError: {e}"),
+                ))
+            }
+            Err(_) => {
+                reached_deadline = true;
+                break;
+            }
+        };
+
+        bytes += chunk.len();
+        total_bytes += chunk.len();
+
+        // Every block is checked against its CID as soon as it's complete
+        let completed_blocks = car_reader.push(&chunk).map_err(|e| {
+            let kind = match e {
+                CarError::BlockHashMismatch { .. } => ErrorKind::BlockHashMismatch,
+                CarError::Parse(_) => ErrorKind::CarParse,
+            };
+            RetrievalError::new(kind, e.to_string())
+        })?;
+        for block in completed_blocks {
+            blocks += 1;
+            block_bytes += block.data_len;
+            if block.verified {
+                verified_blocks += 1;
+            }
+        }
+
+        let current_time = Utc::now();
+        if current_time - retrieval_start_time >= max_duration {
+            info!(
+                "Reached maximum retrieval duration of {:?}, stopping retrieval",
+                max_duration
+            );
+            reached_deadline = true;
+            break;
+        }
+
+        // Save the data for each interval, close to each even second
+        if current_time >= next_log_time {
+            second_by_second_logs.push((
+                current_time,
+                IntervalBytes(bytes),
+                AccumulatingBytes(total_bytes),
+            ));
+            debug!(
+                "Time: {:?}, Bytes retrieved: {}, Blocks: {}",
+                current_time, total_bytes, blocks
+            );
+
+            bytes = 0;
+            next_log_time = calculate_next_interval(current_time, payload.log_interval_ms);
+        }
+    }
+
+    if total_bytes == 0 {
+        return Err(RetrievalError::new(
+            ErrorKind::ZeroBytes,
+            "Retrieved 0 bytes",
+        ));
+    }
+
+    let complete = !reached_deadline && car_reader.is_complete();
+    if !reached_deadline && !complete {
+        return Err(RetrievalError::new(
+            ErrorKind::CarParse,
+            "CarParseError: CAR ended in the middle of a section",
+        ));
+    }
+
+    let end_time = Utc::now();
+    let elapsed_secs = (end_time - retrieval_start_time).num_milliseconds() as f64 / 1000.0;
+    let retrieval_speed = calculate_speed(total_bytes, elapsed_secs);
+
+    info!(
+        "Retrieved {} bytes in {} blocks ({} verified) in {:.2} seconds ({:.2} Mbps)",
+        total_bytes, blocks, verified_blocks, elapsed_secs, retrieval_speed
+    );
+
+    Ok(RetrievalResult {
+        total_bytes,
+        elapsed_secs,
+        retrieval_speed,
+        job_start_time,
+        retrieval_start_time,
+        end_time,
+        time_to_first_byte_ms,
+        second_by_second_logs,
+        blocks,
+        verified_blocks,
+        unverified_blocks: blocks - verified_blocks,
+        block_bytes,
+        complete,
+    })
+}
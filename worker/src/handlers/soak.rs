@@ -0,0 +1,205 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration as StdDuration,
+};
+
+use chrono::Utc;
+use futures::future::join_all;
+use rabbitmq::{ErrorKind, JobMessage, SoakError, SoakResult, SoakSample};
+use reqwest::Client;
+use tokio::time::{interval_at, sleep, timeout, Instant};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::{
+    download::{
+        calculate_speed, check_range_response, header_map, prepare_request, wait_for_start_time,
+    },
+    errors::reqwest_error_kind,
+    protocol::build_client,
+};
+
+const DEFAULT_SAMPLE_INTERVAL_SECS: u64 = 30;
+// Throttled or restarting servers are retried instead of ending the soak
+const RETRY_DELAY: StdDuration = StdDuration::from_secs(1);
+
+/// Failed requests of one stream
+#[derive(Default)]
+struct StreamResult {
+    failed_requests: usize,
+    last_error: Option<SoakError>,
+}
+
+/// Download the range over and over until the deadline, counting the received bytes
+async fn download_until(
+    client: &Client,
+    payload: &JobMessage,
+    deadline: Instant,
+    received_bytes: &AtomicUsize,
+) -> StreamResult {
+    let mut result = StreamResult::default();
+
+    while Instant::now() < deadline {
+        match download_range(client, payload, deadline, received_bytes).await {
+            Ok(()) => {}
+            Err(e) => {
+                debug!("Soak request failed: {}", e.error);
+                result.failed_requests += 1;
+                result.last_error = Some(e);
+                sleep(RETRY_DELAY.min(deadline.saturating_duration_since(Instant::now()))).await;
+            }
+        }
+    }
+
+    result
+}
+
+/// Download the range once, stops without error at the deadline
+async fn download_range(
+    client: &Client,
+    payload: &JobMessage,
+    deadline: Instant,
+    received_bytes: &AtomicUsize,
+) -> Result<(), SoakError> {
+    let request = prepare_request(
+        client,
+        &payload.url,
+        payload.start_range,
+        payload.end_range,
+        header_map(payload, "GET"),
+    )
+    .send();
+    let mut response = match timeout(deadline.saturating_duration_since(Instant::now()), request)
+        .await
+    {
+        Ok(response) => response
+            .map_err(|e| SoakError::new(reqwest_error_kind(&e), format!("RequestError: {e}")))?,
+        Err(_) => return Ok(()),
+    };
+    check_range_response(&response, payload.start_range, payload.end_range)?;
+
+    loop {
+        match timeout(
+            deadline.saturating_duration_since(Instant::now()),
+            response.chunk(),
+        )
+        .await
+        {
+            Ok(Ok(Some(chunk))) => {
+                received_bytes.fetch_add(chunk.len(), Ordering::Relaxed);
+            }
+            Ok(Ok(None)) | Err(_) => return Ok(()),
+            Ok(Err(e)) => {
+                return Err(SoakError::new(
+                    reqwest_error_kind(&e),
+                    format!("ChunkError: {e}"),
+                ))
+            }
+        }
+    }
+}
+
+/// Download continuously for the soak duration and sample the throughput in windows
+///
+/// The windows are aligned to the synchronized start time, so the same window covers the same
+/// time on every worker and the scheduler can add them up.
+#[tracing::instrument(skip(payload))]
+pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<SoakResult, SoakError> {
+    info!("Processing Soak job");
+
+    let sample_interval_secs = payload
+        .sample_interval_secs
+        .map(|secs| secs.max(1) as u64)
+        .unwrap_or(DEFAULT_SAMPLE_INTERVAL_SECS);
+    let sample_interval = StdDuration::from_secs(sample_interval_secs);
+    let soak_duration = StdDuration::from_secs(payload.max_duration_secs.max(1) as u64);
+    let windows = (soak_duration.as_secs() / sample_interval_secs).max(1);
+
+    let streams = payload.streams_per_worker.max(1) as usize;
+    // Every stream keeps its own connection for the whole soak
+    let clients = (0..streams)
+        .map(|_| {
+            build_client(payload.http_protocol.unwrap_or_default())
+                .map_err(|e| SoakError::new(ErrorKind::Internal, format!("ClientError: {e}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let job_start_time = Utc::now();
+
+    // Delay the soak to sync the time on every worker
+    wait_for_start_time(&payload)
+        .await
+        .map_err(|e| SoakError::new(ErrorKind::TimeSync, format!("TimeSyncError: {e}")))?;
+
+    let soak_start_time = Utc::now();
+    let start = Instant::now();
+    let deadline = start + soak_duration;
+    let received_bytes = AtomicUsize::new(0);
+
+    let sampler = async {
+        let mut ticker = interval_at(start + sample_interval, sample_interval);
+        let mut samples = Vec::with_capacity(windows as usize);
+        let mut previous_bytes = 0;
+        for window in 0..windows {
+            ticker.tick().await;
+            let total_bytes = received_bytes.load(Ordering::Relaxed);
+            let bytes = total_bytes - previous_bytes;
+            previous_bytes = total_bytes;
+
+            samples.push(SoakSample {
+                offset_secs: window * sample_interval_secs,
+                bytes,
+                speed: calculate_speed(bytes, sample_interval_secs as f64),
+            });
+        }
+        samples
+    };
+
+    let (stream_results, samples) = tokio::join!(
+        join_all(clients.iter().map(|client| download_until(
+            client,
+            &payload,
+            deadline,
+            &received_bytes
+        ))),
+        sampler
+    );
+
+    let end_time = Utc::now();
+    let elapsed_secs = (end_time - soak_start_time).num_milliseconds() as f64 / 1000.0;
+    let total_bytes = received_bytes.load(Ordering::Relaxed);
+
+    let mut failed_requests = 0;
+    let mut last_error = None;
+    for result in stream_results {
+        failed_requests += result.failed_requests;
+        last_error = result.last_error.or(last_error);
+    }
+
+    if total_bytes == 0 {
+        return Err(last_error.unwrap_or(SoakError::new(
+            ErrorKind::ZeroBytes,
+            "No bytes received during the soak",
+        )));
+    }
+
+    let soak_speed = calculate_speed(total_bytes, elapsed_secs);
+
+    info!(
+        "Soaked {} bytes in {:.2} seconds ({:.2} Mbps, {} failed requests)",
+        total_bytes, elapsed_secs, soak_speed, failed_requests
+    );
+
+    Ok(SoakResult {
+        streams,
+        total_bytes,
+        elapsed_secs,
+        soak_speed,
+        job_start_time,
+        soak_start_time,
+        end_time,
+        failed_requests,
+        sample_interval_secs,
+        samples,
+    })
+}
@@ -0,0 +1,185 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::Result;
+use futures::stream;
+use rabbitmq::{
+    AccumulatingBytes, ErrorKind, IntervalBytes, JobMessage, UploadError, UploadMethod,
+    UploadResult,
+};
+use rand::random;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, USER_AGENT},
+    Body, Client, RequestBuilder,
+};
+use tokio::time::{sleep, timeout};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::{
+    download::{calculate_next_interval, calculate_speed, header_map, wait_for_start_time},
+    errors::reqwest_error_kind,
+};
+
+// Size of the generated chunk, the payload repeats it until the requested size is reached
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Prepare the HTTP request with a generated payload of the given size, the custom headers
+/// replace the default ones
+fn prepare_request(
+    url: &str,
+    method: UploadMethod,
+    size: usize,
+    headers: HeaderMap,
+    sent_bytes: Arc<AtomicUsize>,
+) -> RequestBuilder {
+    const USER_AGENT_STR: &str = "curl/7.68.0";
+    const ACCEPT_TYPE: &str = "*/*";
+    const CONTENT_TYPE_STR: &str = "application/octet-stream";
+
+    let chunk = Bytes::from((0..CHUNK_SIZE).map(|_| random::<u8>()).collect::<Vec<u8>>());
+
+    // Count the bytes as they are handed over to the connection
+    let body = stream::iter((0..size).step_by(CHUNK_SIZE).map(move |offset| {
+        let chunk_size = CHUNK_SIZE.min(size - offset);
+        sent_bytes.fetch_add(chunk_size, Ordering::Relaxed);
+        Ok::<Bytes, std::io::Error>(chunk.slice(..chunk_size))
+    }));
+
+    let client = Client::new();
+    let request = match method {
+        UploadMethod::Put => client.put(url),
+        UploadMethod::Post => client.post(url),
+    };
+
+    let mut request_headers = HeaderMap::new();
+    request_headers.insert(USER_AGENT, HeaderValue::from_static(USER_AGENT_STR));
+    request_headers.insert(ACCEPT, HeaderValue::from_static(ACCEPT_TYPE));
+    request_headers.insert(CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE_STR));
+    request_headers.extend(headers);
+
+    request
+        .headers(request_headers)
+        .header(CONTENT_LENGTH, size)
+        .body(Body::wrap_stream(body))
+}
+
+/// Benchmark the upload speed to the given URL
+#[tracing::instrument(skip(payload))]
+pub async fn process(job_id: Uuid, payload: JobMessage) -> Result<UploadResult, UploadError> {
+    info!("Processing Upload job");
+
+    // For upload jobs the range describes the size of the generated payload
+    let size = (payload.end_range - payload.start_range + 1).max(0) as usize;
+    let sent_bytes = Arc::new(AtomicUsize::new(0));
+    let method = payload.upload_method.unwrap_or_default();
+    let headers = header_map(
+        &payload,
+        match method {
+            UploadMethod::Put => "PUT",
+            UploadMethod::Post => "POST",
+        },
+    );
+    let request = prepare_request(&payload.url, method, size, headers, sent_bytes.clone());
+
+    let job_start_time = Utc::now();
+    let mut second_by_second_logs: Vec<(DateTime<Utc>, IntervalBytes, AccumulatingBytes)> =
+        Vec::new();
+
+    // Delay the upload execution to sync the time on every worker
+    wait_for_start_time(&payload)
+        .await
+        .map_err(|e| UploadError::new(ErrorKind::TimeSync, format!("TimeSyncError: {e}")))?;
+
+    let upload_start_time = Utc::now();
+    let mut next_log_time = calculate_next_interval(upload_start_time, payload.log_interval_ms);
+    let mut logged_bytes: usize = 0;
+
+    // Upload deadline, job will succeed but won't upload more than this duration
+    let max_duration = Duration::seconds(payload.max_duration_secs);
+    let request_future = timeout(max_duration.to_std().unwrap_or_default(), request.send());
+    tokio::pin!(request_future);
+
+    // Save the data for each interval while the request is being sent
+    let response = loop {
+        let sleep_duration = (next_log_time - Utc::now()).to_std().unwrap_or_default();
+
+        tokio::select! {
+            response = &mut request_future => break response,
+            _ = sleep(sleep_duration) => {
+                let current_time = Utc::now();
+                let total_bytes = sent_bytes.load(Ordering::Relaxed);
+
+                second_by_second_logs.push((
+                    current_time,
+                    IntervalBytes(total_bytes - logged_bytes),
+                    AccumulatingBytes(total_bytes),
+                ));
+                debug!("Time: {:?}, Bytes uploaded: {}", current_time, total_bytes);
+
+                logged_bytes = total_bytes;
+                next_log_time = calculate_next_interval(current_time, payload.log_interval_ms);
+            }
+        }
+    };
+
+    let end_time = Utc::now();
+    let total_bytes = sent_bytes.load(Ordering::Relaxed);
+
+    let status_code = match response {
+        Ok(Ok(response)) => {
+            if !response.status().is_success() {
+                return Err(UploadError::new(
+                    ErrorKind::HttpStatus {
+                        code: response.status().as_u16(),
+                    },
+                    format!("RequestFailed: {}", response.status()),
+                ));
+            }
+            Some(response.status().as_u16())
+        }
+        Ok(Err(e)) => {
+            return Err(UploadError::new(
+                reqwest_error_kind(&e),
+                format!("RequestError: {e}"),
+            ))
+        }
+        Err(_) => {
+            info!(
+                "Reached maximum upload duration of {:?}, stopping upload",
+                max_duration
+            );
+            None
+        }
+    };
+
+    if total_bytes == 0 {
+        return Err(UploadError::new(ErrorKind::ZeroBytes, "Uploaded 0 bytes"));
+    }
+
+    let elapsed_secs = (end_time - upload_start_time).num_milliseconds() as f64 / 1000.0;
+    let upload_speed = calculate_speed(total_bytes, elapsed_secs);
+
+    info!(
+        "Uploaded {} bytes in {:.2} seconds ({:.2} Mbps, {:.2} MBps)",
+        total_bytes,
+        elapsed_secs,
+        upload_speed,
+        upload_speed / 8.0
+    );
+
+    Ok(UploadResult {
+        total_bytes,
+        elapsed_secs,
+        upload_speed,
+        job_start_time,
+        upload_start_time,
+        end_time,
+        status_code,
+        second_by_second_logs,
+    })
+}
@@ -11,9 +11,12 @@ use tokio::{
 use tracing::{debug, error, info};
 use tracing_subscriber::EnvFilter;
 
+mod car;
+mod commp;
 mod config;
 mod handlers;
 mod queue;
+mod tcp_info;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -8,9 +8,12 @@ use amqprs::{
 use async_trait::async_trait;
 use chrono::Utc;
 use color_eyre::{eyre::eyre, Result};
-use rabbitmq::{JobMessage, Message, Publisher, ResultMessage, WorkerStatusJobDetails};
+use rabbitmq::{
+    ErrorKind, JobMessage, MeasurementError, Message, Publisher, ResultMessage, WorkerJobType,
+    WorkerStatusJobDetails,
+};
 use serde_json;
-use tokio::time::sleep;
+use tokio::{sync::oneshot, time::sleep};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
@@ -34,7 +37,7 @@ impl JobConsumer {
 
     async fn parse_message(&self, content_str: &str) -> Result<(Uuid, JobMessage)> {
         match serde_json::from_str::<Message>(content_str) {
-            Ok(Message::WorkerJob { job_id, payload }) => Ok((job_id, payload)),
+            Ok(Message::WorkerJob { job_id, payload }) => Ok((job_id, *payload)),
             Ok(_) => Err(eyre!("Received unexpected message")),
             Err(e) => {
                 error!("Error parsing message: {:?}", e);
@@ -88,15 +91,127 @@ impl JobConsumer {
         // Delay the execution to sync the time on every worker
         sleep(sleep_duration.to_std()?).await;
 
-        let (download_result, ping_result, head_result) = tokio::join!(
-            download::process(job_id, job_message.clone()),
-            ping::process(job_id, job_message.clone()),
-            head::process(job_id, job_message.clone()),
-        );
+        // Only the measurement of the job type has a result besides the download, ping and HEAD
+        let mut upload_result = None;
+        let mut retrieval_result = None;
+        let mut iperf3_result = None;
+        let mut random_access_result = None;
+        let mut load_test_result = None;
+        let mut soak_result = None;
+        let not_applicable = |job: &str| {
+            MeasurementError::new(
+                ErrorKind::NotApplicable,
+                format!("Measurement is not part of the {job} job"),
+            )
+        };
+
+        let (download_result, ping_result, head_result) = match job_message.job_type {
+            WorkerJobType::CombinedDHP => {
+                let (download_finished, download_finished_receiver) = oneshot::channel();
+                let (mut download_result, ping_result, head_result, loaded_latency) = tokio::join!(
+                    async {
+                        let result = download::process(job_id, job_message.clone()).await;
+                        let _ = download_finished.send(());
+                        result
+                    },
+                    ping::process(job_id, job_message.clone()),
+                    head::process(job_id, job_message.clone()),
+                    loaded_latency::process(
+                        job_id,
+                        job_message.clone(),
+                        download_finished_receiver
+                    ),
+                );
+                if let Ok(download_result) = download_result.as_mut() {
+                    download_result.loaded_latency = loaded_latency;
+                    // The TLS probe runs once per worker within the HEAD deadline, the download
+                    // shares its negotiated parameters
+                    let head_protocol = head_result
+                        .as_ref()
+                        .ok()
+                        .and_then(|head| head.protocol.as_ref());
+                    if let (Some(download_protocol), Some(head_protocol)) =
+                        (download_result.protocol.as_mut(), head_protocol)
+                    {
+                        download_protocol.alpn.clone_from(&head_protocol.alpn);
+                        download_protocol
+                            .tls_version
+                            .clone_from(&head_protocol.tls_version);
+                    }
+                }
+                (download_result, ping_result, head_result)
+            }
+            WorkerJobType::Upload => {
+                let (result, ping_result, head_result) = tokio::join!(
+                    upload::process(job_id, job_message.clone()),
+                    ping::process(job_id, job_message.clone()),
+                    head::process(job_id, job_message.clone()),
+                );
+                upload_result = Some(result);
+                (Err(not_applicable("upload")), ping_result, head_result)
+            }
+            WorkerJobType::Retrieval => {
+                let (result, ping_result, head_result) = tokio::join!(
+                    retrieval::process(job_id, job_message.clone()),
+                    ping::process(job_id, job_message.clone()),
+                    head::process(job_id, job_message.clone()),
+                );
+                retrieval_result = Some(result);
+                (Err(not_applicable("retrieval")), ping_result, head_result)
+            }
+            // The raw TCP test measures the network alone, the HTTP checks are left out
+            WorkerJobType::Iperf3 => {
+                iperf3_result = Some(iperf3::process(job_id, job_message.clone()).await);
+                (
+                    Err(not_applicable("iperf3")),
+                    Err(not_applicable("iperf3")),
+                    Err(not_applicable("iperf3")),
+                )
+            }
+            WorkerJobType::RandomAccess => {
+                let (result, ping_result, head_result) = tokio::join!(
+                    random_access::process(job_id, job_message.clone()),
+                    ping::process(job_id, job_message.clone()),
+                    head::process(job_id, job_message.clone()),
+                );
+                random_access_result = Some(result);
+                (
+                    Err(not_applicable("random access")),
+                    ping_result,
+                    head_result,
+                )
+            }
+            WorkerJobType::LoadTest => {
+                let (result, ping_result, head_result) = tokio::join!(
+                    load_test::process(job_id, job_message.clone()),
+                    ping::process(job_id, job_message.clone()),
+                    head::process(job_id, job_message.clone()),
+                );
+                load_test_result = Some(result);
+                (Err(not_applicable("load test")), ping_result, head_result)
+            }
+            WorkerJobType::Soak => {
+                let (result, ping_result, head_result) = tokio::join!(
+                    soak::process(job_id, job_message.clone()),
+                    ping::process(job_id, job_message.clone()),
+                    head::process(job_id, job_message.clone()),
+                );
+                soak_result = Some(result);
+                (Err(not_applicable("soak")), ping_result, head_result)
+            }
+        };
 
         debug!(
-            "Results: {:#?} {:#?} {:#?}",
-            ping_result, head_result, download_result,
+            "Results: {:#?} {:#?} {:#?} {:#?} {:#?} {:#?} {:#?} {:#?} {:#?}",
+            ping_result,
+            head_result,
+            download_result,
+            upload_result,
+            retrieval_result,
+            iperf3_result,
+            random_access_result,
+            load_test_result,
+            soak_result,
         );
 
         self.status_sender
@@ -110,11 +225,25 @@ impl JobConsumer {
             job_id,
             sub_job_id,
             worker_name: CONFIG.worker_name.to_string(),
-            // download result is the most important one and determines the success of the job (at least for now)
-            is_success: download_result.is_ok(),
+            // download (or upload, retrieval, iperf3, random access, load test, soak) result is the most important one and determines the success of the job (at least for now)
+            is_success: upload_result
+                .as_ref()
+                .map(Result::is_ok)
+                .or(retrieval_result.as_ref().map(Result::is_ok))
+                .or(iperf3_result.as_ref().map(Result::is_ok))
+                .or(random_access_result.as_ref().map(Result::is_ok))
+                .or(load_test_result.as_ref().map(Result::is_ok))
+                .or(soak_result.as_ref().map(Result::is_ok))
+                .unwrap_or(download_result.is_ok()),
             download_result,
             ping_result,
             head_result,
+            upload_result,
+            retrieval_result,
+            iperf3_result,
+            random_access_result,
+            load_test_result,
+            soak_result,
         })
     }
 
@@ -122,16 +251,27 @@ impl JobConsumer {
         let content_str = String::from_utf8(content)?;
 
         // Parse the received message
-        let (job_id, job_message) = self.parse_message(&content_str).await?;
+        let (job_id, mut job_message) = self.parse_message(&content_str).await?;
 
         if job_message.excluded_workers.contains(&CONFIG.worker_name) {
             info!("Worker is excluded from the job: {}", &CONFIG.worker_name);
             return Ok(());
         }
 
+        // Multi-URL jobs give every worker its own URL and range
+        if let Some(assignment) = job_message.assignments.remove(&CONFIG.worker_name) {
+            info!("Worker is assigned to {}", assignment.url);
+            job_message.url = assignment.url;
+            job_message.start_range = assignment.start_range;
+            job_message.end_range = assignment.end_range;
+        }
+
         // React to the received data
         let result = self.process_message(job_id, job_message).await?;
-        let result_message = Message::WorkerResult { job_id, result };
+        let result_message = Message::WorkerResult {
+            job_id,
+            result: Box::new(result),
+        };
 
         // Publish the result
         if let Err(e) = self
@@ -155,6 +295,15 @@ impl AsyncConsumer for JobConsumer {
         _basic_properties: BasicProperties,
         content: Vec<u8>,
     ) {
+        // Ack the message in any case and before processing, the result is relevant only when the
+        // job is processed immediately, and a soak outlasts the consumer timeout after which the
+        // broker closes the channel of an unacked delivery
+        let args = BasicAckArguments::new(deliver.delivery_tag(), false);
+        match channel.basic_ack(args).await {
+            Ok(()) => debug!("Acked message"),
+            Err(e) => error!("Error acking message: {:?}", e),
+        }
+
         match self.run(content).await {
             Ok(_) => {
                 info!("Message processed successfully");
@@ -163,10 +312,5 @@ impl AsyncConsumer for JobConsumer {
                 error!("Error processing message: {:?}", e);
             }
         }
-
-        // Ack the message in any case. The result will be relevant only when its immediately processed.
-        let args = BasicAckArguments::new(deliver.delivery_tag(), false);
-        channel.basic_ack(args).await.unwrap();
-        debug!("Acked message");
     }
 }
@@ -0,0 +1,156 @@
+/// Socket of an established connection that can be sampled for the kernel TCP_INFO
+///
+/// reqwest does not expose its sockets, so the socket is looked up among the open file
+/// descriptors by the local and peer addresses of the connection the response was received on.
+/// The pair identifies a single TCP connection, parallel connections to the same peer included.
+pub struct TcpInfoSocket {
+    #[cfg(target_os = "linux")]
+    fd: std::os::fd::RawFd,
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        fs,
+        mem::{size_of, MaybeUninit},
+        net::SocketAddr,
+        os::fd::{BorrowedFd, RawFd},
+    };
+
+    use hyper_util::client::legacy::connect::HttpInfo;
+    use rabbitmq::TcpInfoSample;
+    use socket2::SockRef;
+
+    use super::TcpInfoSocket;
+
+    /// Layout of `struct tcp_info` from `linux/tcp.h` up to `tcpi_delivery_rate`
+    #[repr(C)]
+    #[derive(Default)]
+    struct TcpInfo {
+        state: u8,
+        ca_state: u8,
+        retransmits: u8,
+        probes: u8,
+        backoff: u8,
+        options: u8,
+        wscale: u8,
+        app_limited: u8,
+        rto: u32,
+        ato: u32,
+        snd_mss: u32,
+        rcv_mss: u32,
+        unacked: u32,
+        sacked: u32,
+        lost: u32,
+        retrans: u32,
+        fackets: u32,
+        last_data_sent: u32,
+        last_ack_sent: u32,
+        last_data_recv: u32,
+        last_ack_recv: u32,
+        pmtu: u32,
+        rcv_ssthresh: u32,
+        rtt: u32,
+        rttvar: u32,
+        snd_ssthresh: u32,
+        snd_cwnd: u32,
+        advmss: u32,
+        reordering: u32,
+        rcv_rtt: u32,
+        rcv_space: u32,
+        total_retrans: u32,
+        pacing_rate: u64,
+        max_pacing_rate: u64,
+        bytes_acked: u64,
+        bytes_received: u64,
+        segs_out: u32,
+        segs_in: u32,
+        notsent_bytes: u32,
+        min_rtt: u32,
+        data_segs_in: u32,
+        data_segs_out: u32,
+        delivery_rate: u64,
+    }
+
+    /// Local and peer addresses of the socket
+    fn socket_addresses(fd: RawFd) -> Option<(SocketAddr, SocketAddr)> {
+        // The descriptor is owned by the connection, it is only borrowed for the lookup
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        let socket = SockRef::from(&fd);
+
+        Some((
+            socket.local_addr().ok()?.as_socket()?,
+            socket.peer_addr().ok()?.as_socket()?,
+        ))
+    }
+
+    impl TcpInfoSocket {
+        /// Find the socket of the connection the response was received on
+        ///
+        /// The connection stays open while the body of the response is read.
+        pub fn from_response(response: &reqwest::Response) -> Option<Self> {
+            let info = response.extensions().get::<HttpInfo>()?;
+            let addresses = (info.local_addr(), info.remote_addr());
+
+            let fd = fs::read_dir("/proc/self/fd")
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    fs::read_link(entry.path())
+                        .is_ok_and(|link| link.to_string_lossy().starts_with("socket:"))
+                })
+                .filter_map(|entry| entry.file_name().to_str()?.parse::<RawFd>().ok())
+                .find(|fd| socket_addresses(*fd) == Some(addresses))?;
+
+            Some(Self { fd })
+        }
+
+        /// Read the current TCP_INFO of the socket
+        pub fn sample(&self) -> Option<TcpInfoSample> {
+            let mut info = MaybeUninit::new(TcpInfo::default());
+            let mut len = size_of::<TcpInfo>() as libc::socklen_t;
+
+            // Older kernels fill only the beginning of the struct, the rest stays zeroed
+            let result = unsafe {
+                libc::getsockopt(
+                    self.fd,
+                    libc::IPPROTO_TCP,
+                    libc::TCP_INFO,
+                    info.as_mut_ptr() as *mut libc::c_void,
+                    &mut len,
+                )
+            };
+            if result != 0 {
+                return None;
+            }
+            let info = unsafe { info.assume_init() };
+
+            Some(TcpInfoSample {
+                rtt_us: info.rtt,
+                rtt_var_us: info.rttvar,
+                min_rtt_us: info.min_rtt,
+                rcv_rtt_us: info.rcv_rtt,
+                snd_cwnd: info.snd_cwnd,
+                rcv_space: info.rcv_space,
+                retransmits: info.retrans,
+                total_retransmits: info.total_retrans,
+                lost: info.lost,
+                delivery_rate: info.delivery_rate,
+                pacing_rate: info.pacing_rate,
+                bytes_received: info.bytes_received,
+            })
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl TcpInfoSocket {
+    /// TCP_INFO sampling is only supported on Linux
+    pub fn from_response(_response: &reqwest::Response) -> Option<Self> {
+        None
+    }
+
+    pub fn sample(&self) -> Option<rabbitmq::TcpInfoSample> {
+        None
+    }
+}